@@ -0,0 +1,380 @@
+//! Fetching BibTeX entries for arXiv preprints
+//!
+//! Preprints are looked up on INSPIRE by their eprint number first, since INSPIRE entries are of
+//! much better quality.  If INSPIRE does not know about the preprint, or is not to be asked (see
+//! `Api::without_inspire`), an entry is built from the metadata in arXiv's Atom feed.
+//!
+//! In both cases the BibTeX key of the entry is the arXiv identifier that was cited.
+
+use regex::Regex;
+use reqwest::Url;
+use slog::DrainExt;
+
+use crate::http;
+use crate::inspire;
+use crate::xml::Element;
+use crate::InspirerError;
+
+#[derive(Debug, PartialEq)]
+pub struct ArxivId<'a> {
+    pub id: &'a str,
+}
+
+/// Create `ArxivId` from &str
+///
+/// Returns a `Result<Self, ()>` as this can fail.
+///
+/// # Examples
+///
+/// ```
+/// inspirer::arxiv::ArxivId::new("1501.00001").unwrap();
+/// inspirer::arxiv::ArxivId::new("hep-th/9901001").unwrap();
+/// ```
+impl<'a> ArxivId<'a> {
+    #[allow(clippy::result_unit_err)]
+    pub fn new(s: &'a str) -> Result<Self, ()> {
+        if validate_arxiv_id(s) {
+            Ok(ArxivId { id: s })
+        } else {
+            Err(())
+        }
+    }
+
+    /// The identifier without any version suffix
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let id = inspirer::arxiv::ArxivId::new("2103.01234v2").unwrap();
+    /// assert_eq!(id.unversioned(), "2103.01234");
+    /// ```
+    pub fn unversioned(&self) -> &'a str {
+        lazy_static! {
+            static ref VERSION_REGEX: Regex =
+                Regex::new(r"v[[:digit:]]+$").expect("arXiv regex compiled during development");
+        }
+
+        match VERSION_REGEX.find(self.id) {
+            Some(m) => &self.id[..m.start()],
+            None => self.id,
+        }
+    }
+}
+
+/// Test whether a string is a valid arXiv identifier
+///
+/// Both the current scheme (`YYMM.NNNNN`, used since April 2007) and the old `archive/YYMMNNN`
+/// scheme are recognised, with or without a version suffix.
+///
+/// # Examples
+///
+/// ```
+/// assert!(inspirer::arxiv::validate_arxiv_id("1501.00001"));
+/// assert!(inspirer::arxiv::validate_arxiv_id("2103.01234v2"));
+/// assert!(inspirer::arxiv::validate_arxiv_id("hep-th/9901001"));
+/// assert!(!inspirer::arxiv::validate_arxiv_id("Randall:1999ee"));
+/// ```
+pub fn validate_arxiv_id(id: &str) -> bool {
+    lazy_static! {
+        static ref REGEX: Regex = Regex::new(
+            r"^(?:[[:digit:]]{4}\.[[:digit:]]{4,5}|[[:alpha:]-]+(?:\.[[:upper:]]{2})?/[[:digit:]]{7})(?:v[[:digit:]]+)?$"
+        )
+        .expect("arXiv regex compiled during development");
+    }
+
+    REGEX.is_match(id)
+}
+
+pub struct Api {
+    logger: slog::Logger,
+    inspire: Option<inspire::Client>,
+}
+
+impl Api {
+    /// Initialize API, looking records up on INSPIRE with `inspire`
+    ///
+    /// Either provide a custom slog::Logger or default to the standard `log`
+    /// crate.  The client may be shared with other sources.
+    ///
+    /// # Examples
+    /// ```
+    /// use inspirer::inspire;
+    ///
+    /// inspirer::arxiv::Api::init(None, inspire::Client::init(None));
+    /// ```
+    pub fn init(logger: Option<slog::Logger>, inspire: inspire::Client) -> Self {
+        let logger = logger.unwrap_or_else(|| slog::Logger::root(slog_stdlog::StdLog.fuse(), o!()));
+
+        Api {
+            logger,
+            inspire: Some(inspire),
+        }
+    }
+
+    /// Never ask INSPIRE, building every entry from arXiv's metadata
+    ///
+    /// # Examples
+    /// ```
+    /// use inspirer::inspire;
+    ///
+    /// inspirer::arxiv::Api::init(None, inspire::Client::init(None)).without_inspire();
+    /// ```
+    pub fn without_inspire(mut self) -> Self {
+        self.inspire = None;
+        self
+    }
+
+    /// Fetch a BibTeX entry for an arXiv preprint
+    ///
+    /// INSPIRE is asked first, unless `without_inspire` was set; arXiv's own metadata is used as
+    /// a fallback.
+    pub fn fetch_bibtex_with_key(&self, key: ArxivId) -> Result<String, InspirerError> {
+        let inspire = match self.inspire {
+            Some(ref inspire) => inspire,
+            None => return self.fetch_from_arxiv(&key),
+        };
+
+        self.fetch_from_inspire(inspire, &key).or_else(|e| {
+            debug!(self.logger, "Falling back to arXiv";
+                   "key" => key.id, "error" => e.to_string());
+            self.fetch_from_arxiv(&key)
+        })
+    }
+
+    /// Look the preprint up on INSPIRE by its eprint number
+    fn fetch_from_inspire(
+        &self,
+        inspire: &inspire::Client,
+        key: &ArxivId,
+    ) -> Result<String, InspirerError> {
+        let id = inspire::Identifier::Eprint(key.unversioned());
+        let bibtex = inspire.bibtex(id)?;
+
+        replace_key(&bibtex, key.id).ok_or_else(|| InspirerError::MalformedResponse {
+            url: inspire
+                .fetch_url(id, inspire::Serialisation::Bibtex)
+                .to_string(),
+            reason: "response is not a BibTeX entry".to_string(),
+        })
+    }
+
+    /// Build an entry from arXiv's Atom metadata
+    fn fetch_from_arxiv(&self, key: &ArxivId) -> Result<String, InspirerError> {
+        let mut api_url: Url =
+            Url::parse("http://export.arxiv.org/api/query").expect("Static and parseable");
+        api_url.query_pairs_mut().append_pair("id_list", key.id);
+
+        let atom = http::get(&self.logger, api_url.clone(), key.id, "arXiv")?;
+
+        atom_to_bibtex(key.id, &atom).ok_or_else(|| {
+            if is_error_feed(&atom) {
+                InspirerError::NotFound {
+                    key: key.id.to_string(),
+                    source: "arXiv",
+                }
+            } else {
+                InspirerError::MalformedResponse {
+                    url: api_url.to_string(),
+                    reason: "no preprint in Atom feed".to_string(),
+                }
+            }
+        })
+    }
+}
+
+/// Replace the key of a BibTeX entry
+pub(crate) fn replace_key(entry: &str, key: &str) -> Option<String> {
+    lazy_static! {
+        static ref KEY_REGEX: Regex = Regex::new(r"^\s*(?P<head>@[[:alpha:]]+\s*\{)\s*[^,\s]*\s*,")
+            .expect("BibTeX key regex compiled during development");
+    }
+
+    let head = KEY_REGEX.captures(entry)?;
+    let rest = &entry[head.get(0)?.end()..];
+
+    Some(format!("{}{},{}", &head["head"], key, rest))
+}
+
+/// Build a BibTeX entry from an arXiv Atom feed
+///
+/// Returns `None` if the feed does not contain a preprint, which is how arXiv reports unknown
+/// identifiers.
+fn atom_to_bibtex(key: &str, atom: &str) -> Option<String> {
+    let document = Element::parse(atom);
+    let entry = *document.find("entry").first()?;
+
+    let text_of = |name: &str| -> Option<String> {
+        entry
+            .find(name)
+            .first()
+            .map(|n| collapse_whitespace(&n.text()))
+            .filter(|s| !s.is_empty())
+    };
+
+    if is_error_feed(atom) {
+        return None;
+    }
+
+    let authors = entry
+        .find("author")
+        .iter()
+        .filter_map(|a| a.find("name").first().cloned())
+        .map(|n| invert_name(&collapse_whitespace(&n.text())))
+        .collect::<Vec<String>>()
+        .join(" and ");
+    let title = text_of("title")?;
+    let year = text_of("published")?.chars().take(4).collect::<String>();
+    let primary_class = entry
+        .find("arxiv:primary_category")
+        .first()
+        .and_then(|n| n.attr("term").map(|t| t.to_string()));
+    let doi = text_of("arxiv:doi");
+
+    let mut fields = vec![
+        ("author", authors),
+        ("title", format!("{{{}}}", title)),
+        ("year", year),
+        ("eprint", key.to_string()),
+        ("archivePrefix", "arXiv".to_string()),
+    ];
+    if let Some(primary_class) = primary_class {
+        fields.push(("primaryClass", primary_class));
+    }
+    if let Some(doi) = doi {
+        fields.push(("doi", doi));
+    }
+
+    let fields = fields
+        .iter()
+        .map(|(name, value)| format!("      {:<15}= \"{}\"", name, escape_quotes(value)))
+        .collect::<Vec<String>>()
+        .join(",\n");
+
+    Some(format!("@article{{{},\n{}\n}}\n", key, fields))
+}
+
+/// arXiv answers unknown identifiers with an entry pointing at its error documentation
+fn is_error_feed(atom: &str) -> bool {
+    Element::parse(atom)
+        .find("entry")
+        .first()
+        .and_then(|entry| entry.find("id").first().map(|id| id.text()))
+        .is_some_and(|id| id.contains("/api/errors"))
+}
+
+/// Protect the `"` in a field value, which would otherwise end it
+fn escape_quotes(value: &str) -> String {
+    value.replace('"', "{\"}")
+}
+
+fn collapse_whitespace(s: &str) -> String {
+    s.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+/// Turn "First Last" into "Last, First"
+fn invert_name(name: &str) -> String {
+    match name.rfind(' ') {
+        Some(i) => format!("{}, {}", &name[i + 1..], &name[..i]),
+        None => name.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ATOM: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<feed xmlns="http://www.w3.org/2005/Atom">
+  <title type="html">ArXiv Query: search_query=&amp;id_list=hep-th/9711200</title>
+  <entry>
+    <id>http://arxiv.org/abs/hep-th/9711200v3</id>
+    <updated>1998-01-22T22:11:55Z</updated>
+    <published>1997-11-27T21:13:53Z</published>
+    <title>The Large N Limit of Superconformal Field Theories and
+      Supergravity</title>
+    <summary>We show that the large N limit of certain conformal field theories...</summary>
+    <author>
+      <name>Juan M. Maldacena</name>
+    </author>
+    <arxiv:doi xmlns:arxiv="http://arxiv.org/schemas/atom">10.1023/A:1026654312961</arxiv:doi>
+    <link title="doi" href="http://dx.doi.org/10.1023/A:1026654312961" rel="related"/>
+    <link href="http://arxiv.org/abs/hep-th/9711200v3" rel="alternate" type="text/html"/>
+    <arxiv:primary_category xmlns:arxiv="http://arxiv.org/schemas/atom" term="hep-th" scheme="http://arxiv.org/schemas/atom"/>
+    <category term="hep-th" scheme="http://arxiv.org/schemas/atom"/>
+  </entry>
+</feed>
+"#;
+
+    #[test]
+    fn test_validate_new_style() {
+        assert!(validate_arxiv_id("0704.0001"));
+        assert!(validate_arxiv_id("1501.00001"));
+        assert!(validate_arxiv_id("2103.01234v2"));
+        assert!(!validate_arxiv_id("1501.001"));
+        assert!(!validate_arxiv_id("1501.00001v"));
+    }
+
+    #[test]
+    fn test_validate_old_style() {
+        assert!(validate_arxiv_id("hep-th/9901001"));
+        assert!(validate_arxiv_id("math.GT/0309136"));
+        assert!(validate_arxiv_id("astro-ph/0101001v1"));
+        assert!(!validate_arxiv_id("hep-th/990100"));
+    }
+
+    #[test]
+    fn test_validate_other_keys() {
+        assert!(!validate_arxiv_id("Randall:1999ee"));
+        assert!(!validate_arxiv_id("1999PhRvL..83.3370R"));
+        assert!(!validate_arxiv_id(""));
+    }
+
+    #[test]
+    fn test_replace_key() {
+        let entry = "@article{Maldacena:1997re,\n    author = \"Maldacena, Juan Martin\"\n}\n";
+
+        assert_eq!(
+            replace_key(entry, "hep-th/9711200").unwrap(),
+            "@article{hep-th/9711200,\n    author = \"Maldacena, Juan Martin\"\n}\n"
+        );
+        assert_eq!(replace_key("not bibtex", "hep-th/9711200"), None);
+    }
+
+    #[test]
+    fn test_atom_to_bibtex() {
+        let bibtex = atom_to_bibtex("hep-th/9711200", ATOM).unwrap();
+
+        assert!(bibtex.starts_with("@article{hep-th/9711200,\n"));
+        assert!(bibtex.contains("author         = \"Maldacena, Juan M.\""));
+        assert!(bibtex.contains(
+            "title          = \"{The Large N Limit of Superconformal Field Theories and Supergravity}\""
+        ));
+        assert!(bibtex.contains("year           = \"1997\""));
+        assert!(bibtex.contains("primaryClass   = \"hep-th\""));
+        assert!(bibtex.contains("doi            = \"10.1023/A:1026654312961\""));
+    }
+
+    #[test]
+    fn test_atom_to_bibtex_quotes() {
+        let atom = ATOM.replace("The Large N Limit", "The \"Large N\" Limit");
+        let bibtex = atom_to_bibtex("hep-th/9711200", &atom).unwrap();
+
+        assert!(bibtex.contains(
+            "title          = \"{The {\"}Large N{\"} Limit of Superconformal Field Theories and Supergravity}\""
+        ));
+    }
+
+    #[test]
+    fn test_atom_to_bibtex_error() {
+        let atom = r#"<feed xmlns="http://www.w3.org/2005/Atom">
+  <entry>
+    <id>http://arxiv.org/api/errors#incorrect_id_format_for_1501.0001</id>
+    <title>Error</title>
+  </entry>
+</feed>"#;
+
+        assert_eq!(atom_to_bibtex("1501.0001", atom), None);
+        assert!(is_error_feed(atom));
+        assert!(!is_error_feed(ATOM));
+    }
+}
@@ -0,0 +1,452 @@
+//! Parsing LaTeX .aux files
+//!
+//! An .aux file is a sequence of TeX macro calls.  Rather than scraping it with regular
+//! expressions, it is split into tokens (control sequences, brace groups and single characters),
+//! from which an [`AuxDocument`](struct.AuxDocument.html) is built.
+
+/// A citation in an .aux file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Citation {
+    pub key: String,
+    /// The BibLaTeX refsection, or `None` for BibTeX citations
+    pub refsection: Option<u32>,
+    /// Line of the .aux file on which the citation appears, starting from 1
+    pub line: usize,
+}
+
+/// A label assigned to a key by `\bibcite`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BibCite {
+    pub key: String,
+    pub label: String,
+    pub line: usize,
+}
+
+/// The contents of a LaTeX .aux file
+///
+/// # Examples
+///
+/// ```
+/// use inspirer::auxfile::AuxDocument;
+///
+/// let aux = AuxDocument::parse(
+/// r"\relax
+/// \@input{chapter1.aux}
+/// \citation{Higgs:2014aqa,Higgs:2015mei}
+/// \bibstyle{unsrt}
+/// \bibdata{refs,extra}");
+///
+/// assert_eq!(aux.keys(), vec!["Higgs:2014aqa", "Higgs:2015mei"]);
+/// assert_eq!(aux.citations[0].line, 3);
+/// assert_eq!(aux.bibstyle, Some("unsrt".to_string()));
+/// assert_eq!(aux.bibdata, vec!["refs", "extra"]);
+/// assert_eq!(aux.inputs, vec!["chapter1.aux"]);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AuxDocument {
+    /// Citations in the order they appear
+    pub citations: Vec<Citation>,
+    /// Databases named by `\bibdata`
+    pub bibdata: Vec<String>,
+    /// Style named by `\bibstyle`
+    pub bibstyle: Option<String>,
+    /// Labels assigned by `\bibcite`
+    pub bibcites: Vec<BibCite>,
+    /// Child .aux files included with `\@input`
+    pub inputs: Vec<String>,
+    /// BibLaTeX sorting scheme
+    ///
+    /// Taken from `\abx@aux@sortscheme`, or from the first `\abx@aux@refcontext` for versions of
+    /// biblatex which no longer write the former.
+    pub sort_scheme: Option<String>,
+    /// BibLaTeX reference contexts named by `\abx@aux@refcontext`
+    pub refcontexts: Vec<String>,
+}
+
+impl AuxDocument {
+    /// Parse the contents of an .aux file
+    ///
+    /// Parsing never fails; anything which is not understood is skipped.
+    pub fn parse(input: &str) -> Self {
+        let tokens: Vec<Token> = Tokenizer::new(input).collect();
+        let mut doc = AuxDocument::default();
+
+        // Old biblatex citations, whose refsection is given by a following \abx@aux@segm
+        let mut pending: Vec<usize> = Vec::new();
+
+        let mut i = 0;
+        while i < tokens.len() {
+            let (name, line) = match tokens[i] {
+                Token::ControlSequence(ref name, line) => (name.as_str(), line),
+                _ => {
+                    i += 1;
+                    continue;
+                }
+            };
+            i += 1;
+
+            match name {
+                "citation" => {
+                    if let Some(keys) = arguments(&tokens, &mut i, 1).pop() {
+                        for key in split_list(&keys) {
+                            doc.citations.push(Citation {
+                                key,
+                                refsection: None,
+                                line,
+                            });
+                        }
+                    }
+                }
+                "abx@aux@cite" => {
+                    let mut args = arguments(&tokens, &mut i, 2);
+                    let new_syntax = args.len() == 2 && args[0].trim().parse::<u32>().is_ok();
+
+                    if new_syntax {
+                        doc.citations.push(Citation {
+                            key: args[1].trim().to_string(),
+                            refsection: args[0].trim().parse().ok(),
+                            line,
+                        });
+                    } else if !args.is_empty() {
+                        // Only the first group belonged to the citation
+                        let key = args.swap_remove(0);
+                        pending.push(doc.citations.len());
+                        doc.citations.push(Citation {
+                            key: key.trim().to_string(),
+                            refsection: Some(0),
+                            line,
+                        });
+                    }
+                }
+                "abx@aux@segm" => {
+                    let args = arguments(&tokens, &mut i, 3);
+                    if args.len() == 3 {
+                        let key = args[2].trim();
+                        if let Some(p) = pending.iter().position(|&c| doc.citations[c].key == key) {
+                            let c = pending.remove(p);
+                            doc.citations[c].refsection = args[0].trim().parse().ok().or(Some(0));
+                        }
+                    }
+                }
+                "abx@aux@sortscheme" => {
+                    if let Some(scheme) = arguments(&tokens, &mut i, 1).pop() {
+                        doc.sort_scheme = Some(scheme.trim().to_string());
+                    }
+                }
+                "abx@aux@refcontext" => {
+                    if let Some(context) = arguments(&tokens, &mut i, 1).pop() {
+                        doc.refcontexts.push(context.trim().to_string());
+                    }
+                }
+                "bibdata" => {
+                    if let Some(databases) = arguments(&tokens, &mut i, 1).pop() {
+                        doc.bibdata.extend(split_list(&databases));
+                    }
+                }
+                "bibstyle" => {
+                    if let Some(style) = arguments(&tokens, &mut i, 1).pop() {
+                        doc.bibstyle = Some(style.trim().to_string());
+                    }
+                }
+                "bibcite" => {
+                    let args = arguments(&tokens, &mut i, 2);
+                    if args.len() == 2 {
+                        doc.bibcites.push(BibCite {
+                            key: args[0].trim().to_string(),
+                            label: args[1].clone(),
+                            line,
+                        });
+                    }
+                }
+                "@input" => {
+                    if let Some(file) = arguments(&tokens, &mut i, 1).pop() {
+                        doc.inputs.push(file.trim().to_string());
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if doc.sort_scheme.is_none() {
+            doc.sort_scheme = doc
+                .refcontexts
+                .first()
+                .and_then(|c| c.split('/').next())
+                .filter(|s| !s.is_empty())
+                .map(|s| s.to_string());
+        }
+
+        doc
+    }
+
+    /// Cited keys, sorted alphabetically and without duplicates
+    pub fn keys(&self) -> Vec<String> {
+        let mut keys: Vec<String> = self.citations.iter().map(|c| c.key.clone()).collect();
+
+        keys.sort_unstable();
+        keys.dedup();
+
+        keys
+    }
+}
+
+/// Read up to `n` brace-delimited arguments following a control sequence
+///
+/// Whitespace between arguments is skipped, as TeX does.  `i` is left pointing after the last
+/// argument read.
+fn arguments(tokens: &[Token], i: &mut usize, n: usize) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut j = *i;
+
+    while args.len() < n && j < tokens.len() {
+        match tokens[j] {
+            Token::Group(ref content, _) => {
+                args.push(content.clone());
+                j += 1;
+                *i = j;
+            }
+            Token::Character(c, _) if c.is_whitespace() => j += 1,
+            _ => break,
+        }
+    }
+
+    args
+}
+
+/// Split a comma-separated list such as the argument of `\citation` or `\bibdata`
+fn split_list(list: &str) -> Vec<String> {
+    list.split(',')
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .map(|s| s.to_string())
+        .collect()
+}
+
+/// A TeX token, together with the line on which it starts
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    /// A control sequence, without the leading backslash
+    ControlSequence(String, usize),
+    /// The contents of a brace group, without the outer braces or comments
+    Group(String, usize),
+    /// Any other character
+    Character(char, usize),
+}
+
+struct Tokenizer<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+    line: usize,
+}
+
+impl<'a> Tokenizer<'a> {
+    fn new(input: &'a str) -> Self {
+        Tokenizer {
+            chars: input.chars().peekable(),
+            line: 1,
+        }
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.chars.next();
+        if c == Some('\n') {
+            self.line += 1;
+        }
+        c
+    }
+
+    /// Skip to the end of the line, leaving the newline itself
+    fn skip_comment(&mut self) {
+        while let Some(&c) = self.chars.peek() {
+            if c == '\n' {
+                break;
+            }
+            self.bump();
+        }
+    }
+
+    /// Read a control sequence name following a backslash
+    ///
+    /// In .aux files `@` is a letter, so it may appear in names.
+    fn control_sequence(&mut self) -> String {
+        let mut name = String::new();
+
+        while let Some(&c) = self.chars.peek() {
+            if c.is_ascii_alphabetic() || c == '@' {
+                name.push(c);
+                self.bump();
+            } else {
+                break;
+            }
+        }
+
+        // Control symbols consist of a single non-letter
+        if name.is_empty() {
+            if let Some(c) = self.bump() {
+                name.push(c);
+            }
+        }
+
+        name
+    }
+
+    /// Read a group following an opening brace, up to the matching closing brace
+    fn group(&mut self) -> String {
+        let mut content = String::new();
+        let mut depth = 0;
+
+        while let Some(c) = self.bump() {
+            match c {
+                '\\' => {
+                    content.push(c);
+                    if let Some(escaped) = self.bump() {
+                        content.push(escaped);
+                    }
+                }
+                '%' => self.skip_comment(),
+                '{' => {
+                    depth += 1;
+                    content.push(c);
+                }
+                '}' if depth == 0 => break,
+                '}' => {
+                    depth -= 1;
+                    content.push(c);
+                }
+                _ => content.push(c),
+            }
+        }
+
+        content
+    }
+}
+
+impl<'a> Iterator for Tokenizer<'a> {
+    type Item = Token;
+
+    fn next(&mut self) -> Option<Token> {
+        loop {
+            let line = self.line;
+
+            match self.bump()? {
+                '%' => self.skip_comment(),
+                '\\' => return Some(Token::ControlSequence(self.control_sequence(), line)),
+                '{' => return Some(Token::Group(self.group(), line)),
+                c => return Some(Token::Character(c, line)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokenizer() {
+        let tokens: Vec<Token> =
+            Tokenizer::new("\\relax \\bibcite{a}{{1}{}}% comment\n\\@input{b}").collect();
+
+        assert_eq!(
+            tokens,
+            vec![
+                Token::ControlSequence("relax".to_string(), 1),
+                Token::Character(' ', 1),
+                Token::ControlSequence("bibcite".to_string(), 1),
+                Token::Group("a".to_string(), 1),
+                Token::Group("{1}{}".to_string(), 1),
+                Token::Character('\n', 1),
+                Token::ControlSequence("@input".to_string(), 2),
+                Token::Group("b".to_string(), 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenizer_escaped_braces() {
+        let tokens: Vec<Token> = Tokenizer::new(r"{a\}b}").collect();
+
+        assert_eq!(tokens, vec![Token::Group(r"a\}b".to_string(), 1)]);
+    }
+
+    #[test]
+    fn test_commented_citation() {
+        let aux = AuxDocument::parse(
+            "\\citation{Higgs:2014aqa}\n% \\citation{Higgs:2015mei}\n\\citation{Guth:1980zm}",
+        );
+
+        assert_eq!(aux.keys(), vec!["Guth:1980zm", "Higgs:2014aqa"]);
+        assert_eq!(aux.citations[1].line, 3);
+    }
+
+    #[test]
+    fn test_bibcite() {
+        let aux = AuxDocument::parse("\\bibcite{Higgs:2014aqa}{{1}{2014}{{Higgs}}{{}}}");
+
+        assert_eq!(
+            aux.bibcites,
+            vec![BibCite {
+                key: "Higgs:2014aqa".to_string(),
+                label: "{1}{2014}{{Higgs}}{{}}".to_string(),
+                line: 1,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_example_bibtex() {
+        let aux = AuxDocument::parse(include_str!("../example_files/test_bibtex.aux"));
+
+        assert_eq!(aux.keys(), vec!["Higgs:2014aqa", "Higgs:2015mei"]);
+        assert_eq!(aux.citations[0].refsection, None);
+        assert_eq!(aux.bibstyle, Some("unsrt".to_string()));
+        assert_eq!(aux.bibdata, vec!["test_bibtex"]);
+        assert_eq!(aux.sort_scheme, None);
+    }
+
+    #[test]
+    fn test_example_biber() {
+        let aux = AuxDocument::parse(include_str!("../example_files/test_biber.aux"));
+
+        assert_eq!(
+            aux.keys(),
+            vec![
+                "1982PhRvL..48.1220A",
+                "Albrecht:1982wi",
+                "Guth:1980zm",
+                "Linde:1981mu"
+            ]
+        );
+        assert!(aux.citations.iter().all(|c| c.refsection == Some(0)));
+        assert_eq!(aux.sort_scheme, Some("nty".to_string()));
+        assert_eq!(aux.refcontexts, vec!["nty/global/"]);
+        assert!(aux.bibdata.is_empty());
+    }
+
+    #[test]
+    fn test_new_biblatex_syntax() {
+        let aux = AuxDocument::parse(
+            r"\abx@aux@refcontext{ynt/global//global/global}
+\abx@aux@cite{0}{Guth:1980zm}
+\abx@aux@segm{0}{0}{Guth:1980zm}
+\abx@aux@cite{1}{Linde:1981mu}
+\abx@aux@segm{1}{0}{Linde:1981mu}",
+        );
+
+        assert_eq!(
+            aux.citations,
+            vec![
+                Citation {
+                    key: "Guth:1980zm".to_string(),
+                    refsection: Some(0),
+                    line: 2,
+                },
+                Citation {
+                    key: "Linde:1981mu".to_string(),
+                    refsection: Some(1),
+                    line: 4,
+                },
+            ]
+        );
+        assert_eq!(aux.sort_scheme, Some("ynt".to_string()));
+    }
+}
@@ -0,0 +1,202 @@
+//! Parsing biber control files
+//!
+//! biblatex writes a `.bcf` file for biber, listing the cited keys of each refsection and the
+//! data sources to read them from.  This is the authoritative record of what a biblatex
+//! document cites, so it is more reliable than the `\abx@aux@cite` lines in the .aux file.
+
+use crate::xml::Element;
+
+/// The cited keys of a refsection
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Section {
+    pub number: u32,
+    /// Keys in the order they were cited, without duplicates
+    pub citekeys: Vec<String>,
+}
+
+/// A data source declared with `\addbibresource` or `\bibliography`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Datasource {
+    /// The refsection the data source belongs to
+    pub section: u32,
+    /// File name or URL, as given in the document
+    pub location: String,
+    /// Kind of location, usually "file"
+    pub kind: String,
+    /// Format of the data, usually "bibtex"
+    pub datatype: String,
+}
+
+/// The contents of a biber control file
+///
+/// # Examples
+///
+/// ```
+/// use inspirer::bcf::BcfDocument;
+///
+/// let bcf = BcfDocument::parse(r#"<?xml version="1.0" encoding="UTF-8"?>
+/// <bcf:controlfile version="3.7" xmlns:bcf="https://sourceforge.net/projects/biblatex">
+///   <bcf:bibdata section="0">
+///     <bcf:datasource type="file" datatype="bibtex">refs.bib</bcf:datasource>
+///   </bcf:bibdata>
+///   <bcf:section number="0">
+///     <bcf:citekey order="1">Guth:1980zm</bcf:citekey>
+///   </bcf:section>
+/// </bcf:controlfile>"#);
+///
+/// assert_eq!(bcf.sections[0].citekeys, vec!["Guth:1980zm"]);
+/// assert_eq!(bcf.datasources[0].location, "refs.bib");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BcfDocument {
+    pub sections: Vec<Section>,
+    pub datasources: Vec<Datasource>,
+}
+
+impl BcfDocument {
+    /// Parse the contents of a .bcf file
+    ///
+    /// Parsing never fails; elements which are not understood are skipped.
+    pub fn parse(input: &str) -> Self {
+        let document = Element::parse(input);
+
+        let mut sections: Vec<Section> = Vec::new();
+        for node in document.find("bcf:section") {
+            let number = section_number(node, "number");
+
+            let mut citekeys: Vec<String> = Vec::new();
+            for key in node.find("bcf:citekey") {
+                let key = key.text().trim().to_string();
+                // \nocite{*} is recorded as the key "*"
+                if !key.is_empty() && key != "*" && !citekeys.contains(&key) {
+                    citekeys.push(key);
+                }
+            }
+
+            // A refsection may be split across several elements
+            match sections.iter_mut().find(|s| s.number == number) {
+                Some(section) => {
+                    for key in citekeys {
+                        if !section.citekeys.contains(&key) {
+                            section.citekeys.push(key);
+                        }
+                    }
+                }
+                None => sections.push(Section { number, citekeys }),
+            }
+        }
+
+        let mut datasources: Vec<Datasource> = Vec::new();
+        for bibdata in document.find("bcf:bibdata") {
+            let section = section_number(bibdata, "section");
+
+            for node in bibdata.find("bcf:datasource") {
+                datasources.push(Datasource {
+                    section,
+                    location: node.text().trim().to_string(),
+                    kind: node.attr("type").unwrap_or("file").to_string(),
+                    datatype: node.attr("datatype").unwrap_or("bibtex").to_string(),
+                });
+            }
+        }
+
+        BcfDocument {
+            sections,
+            datasources,
+        }
+    }
+
+    /// Cited keys of all sections, sorted alphabetically and without duplicates
+    pub fn keys(&self) -> Vec<String> {
+        let mut keys: Vec<String> = self
+            .sections
+            .iter()
+            .flat_map(|s| s.citekeys.iter().cloned())
+            .collect();
+
+        keys.sort_unstable();
+        keys.dedup();
+
+        keys
+    }
+}
+
+fn section_number(node: &Element, attr: &str) -> u32 {
+    node.attr(attr)
+        .and_then(|n| n.trim().parse().ok())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_example_biber() {
+        let bcf = BcfDocument::parse(include_str!("../example_files/test_biber.bcf"));
+
+        assert_eq!(
+            bcf.sections,
+            vec![Section {
+                number: 0,
+                citekeys: vec![
+                    "Guth:1980zm".to_string(),
+                    "Linde:1981mu".to_string(),
+                    "Albrecht:1982wi".to_string(),
+                    "1982PhRvL..48.1220A".to_string(),
+                ],
+            }]
+        );
+        assert_eq!(
+            bcf.datasources,
+            vec![Datasource {
+                section: 0,
+                location: "test_biber.bib".to_string(),
+                kind: "file".to_string(),
+                datatype: "bibtex".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_sections() {
+        let bcf = BcfDocument::parse(
+            r#"<bcf:controlfile xmlns:bcf="https://sourceforge.net/projects/biblatex">
+  <bcf:bibdata section="0">
+    <bcf:datasource type="file" datatype="bibtex">main.bib</bcf:datasource>
+  </bcf:bibdata>
+  <bcf:bibdata section="1">
+    <bcf:datasource type="file" datatype="bibtex">appendix.bib</bcf:datasource>
+    <bcf:datasource type="url" datatype="bibtex">http://example.com/extra.bib</bcf:datasource>
+  </bcf:bibdata>
+  <bcf:section number="0">
+    <bcf:citekey order="1">Guth:1980zm</bcf:citekey>
+    <bcf:citekey order="2">Guth:1980zm</bcf:citekey>
+    <bcf:citekey order="0" nocite="1">*</bcf:citekey>
+  </bcf:section>
+  <bcf:section number="1">
+    <bcf:citekey order="1">Linde:1981mu</bcf:citekey>
+    <bcf:citekey order="2">Guth:1980zm</bcf:citekey>
+  </bcf:section>
+</bcf:controlfile>"#,
+        );
+
+        assert_eq!(bcf.sections.len(), 2);
+        assert_eq!(bcf.sections[0].citekeys, vec!["Guth:1980zm"]);
+        assert_eq!(bcf.sections[1].number, 1);
+        assert_eq!(
+            bcf.sections[1].citekeys,
+            vec!["Linde:1981mu", "Guth:1980zm"]
+        );
+        assert_eq!(bcf.keys(), vec!["Guth:1980zm", "Linde:1981mu"]);
+
+        assert_eq!(bcf.datasources.len(), 3);
+        assert_eq!(bcf.datasources[2].section, 1);
+        assert_eq!(bcf.datasources[2].kind, "url");
+    }
+
+    #[test]
+    fn test_empty() {
+        assert_eq!(BcfDocument::parse(""), BcfDocument::default());
+    }
+}
@@ -0,0 +1,969 @@
+//! Reading and writing BibTeX
+//!
+//! [`Entry`](struct.Entry.html) is a parsed BibTeX entry, as returned by `Inspirer::bibtex`.
+//! [`entries`](fn.entries.html) only locates entries and their keys in a database, which is
+//! enough to tell whether it already contains an entry and to replace it, and
+//! [`string_spans`](fn.string_spans.html) likewise locates `@string` definitions.  Anything
+//! outside an entry is a comment to BibTeX and is left alone.
+
+use std::error;
+use std::fmt;
+use std::ops::Range;
+
+/// The value of a field
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    /// Text in braces or quotes, without the outer delimiters
+    Text(String),
+    /// A bare number, such as `year = 2014`
+    Number(String),
+    /// A string abbreviation, such as `month = apr`
+    Macro(String),
+    /// Values joined with `#`
+    Concat(Vec<Value>),
+}
+
+impl Value {
+    /// The text of a `Text` or `Number`, as BibTeX would see it
+    ///
+    /// Abbreviations and concatenations cannot be expanded without the database they are defined
+    /// in, so give `None`.
+    pub fn as_str(&self) -> Option<&str> {
+        match *self {
+            Value::Text(ref text) | Value::Number(ref text) => Some(text),
+            Value::Macro(_) | Value::Concat(_) => None,
+        }
+    }
+
+    /// The value with the abbreviations defined in `strings` replaced by their values
+    ///
+    /// Abbreviations are matched ignoring case, as BibTeX does, and the last definition of a name
+    /// is used.  Undefined abbreviations, such as the months which BibTeX styles define, are kept.
+    /// Concatenations which are left with only text are joined.
+    pub fn expand(&self, strings: &[(String, Value)]) -> Value {
+        match *self {
+            Value::Macro(ref name) => strings
+                .iter()
+                .rev()
+                .find(|(n, _)| n.eq_ignore_ascii_case(name))
+                .map_or_else(|| self.clone(), |(_, value)| value.clone()),
+            Value::Concat(ref values) => {
+                let values: Vec<Value> = values.iter().map(|v| v.expand(strings)).collect();
+                match values
+                    .iter()
+                    .map(Value::as_str)
+                    .collect::<Option<Vec<&str>>>()
+                {
+                    Some(parts) => Value::Text(parts.concat()),
+                    None => Value::Concat(values),
+                }
+            }
+            Value::Text(_) | Value::Number(_) => self.clone(),
+        }
+    }
+
+    /// The names of the abbreviations the value uses, in order
+    pub fn macros(&self) -> Vec<&str> {
+        match *self {
+            Value::Macro(ref name) => vec![name],
+            Value::Concat(ref values) => values.iter().flat_map(Value::macros).collect(),
+            Value::Text(_) | Value::Number(_) => Vec::new(),
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Value::Text(ref text) => write!(f, "{{{}}}", text),
+            Value::Number(ref number) => write!(f, "{}", number),
+            Value::Macro(ref name) => write!(f, "{}", name),
+            Value::Concat(ref values) => {
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, " # ")?;
+                    }
+                    write!(f, "{}", value)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// A field of an entry, such as `title = {...}`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Field {
+    pub name: String,
+    pub value: Value,
+}
+
+/// A BibTeX entry
+///
+/// Parsed entries remember their text, from the `@` to the closing delimiter, which `raw` gives
+/// back unchanged as long as the entry is not modified.  `Display` writes the entry in a uniform
+/// layout instead.
+///
+/// # Examples
+///
+/// ```
+/// use inspirer::bib::{Entry, Value};
+///
+/// let text = "@article{Higgs:2014aqa,\n      author = \"Higgs, Peter W.\",\n      year = 2014\n}\n";
+/// let mut entry = Entry::parse(text).unwrap();
+///
+/// assert_eq!(entry.kind(), "article");
+/// assert_eq!(entry.key(), "Higgs:2014aqa");
+/// assert_eq!(entry.get("Author").and_then(Value::as_str), Some("Higgs, Peter W."));
+/// assert_eq!(entry.raw(), Some(text.trim_end()));
+///
+/// entry.set("year", Value::Number("2015".to_string()));
+/// assert_eq!(entry.raw(), None);
+/// assert_eq!(
+///     entry.to_string(),
+///     "@article{Higgs:2014aqa,\n  author = {Higgs, Peter W.},\n  year = 2015\n}\n"
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    kind: String,
+    key: String,
+    fields: Vec<Field>,
+    raw: Option<String>,
+    dependencies: Dependencies,
+}
+
+/// The text an entry needs from the database it was taken from, see
+/// `Entry::parse_with_dependencies`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Dependencies {
+    /// `@string` definitions, by the name they define, which must come before the entry
+    pub strings: Vec<(String, String)>,
+    /// Entries named by `crossref`, by key, which must come after the entry
+    pub parents: Vec<(String, String)>,
+}
+
+impl Entry {
+    /// A new entry without fields
+    pub fn new(kind: &str, key: &str) -> Self {
+        Entry {
+            kind: kind.to_string(),
+            key: key.to_string(),
+            fields: Vec::new(),
+            raw: None,
+            dependencies: Dependencies::default(),
+        }
+    }
+
+    /// Parse the first entry in `text`
+    ///
+    /// Text before the entry is skipped, as BibTeX does; text after it is ignored.  `@string`,
+    /// `@preamble` and `@comment` are not entries and are skipped too.
+    pub fn parse(text: &str) -> Result<Self, ParseError> {
+        let mut parser = Parser { text, pos: 0 };
+
+        loop {
+            parser.pos += match text[parser.pos..].find('@') {
+                Some(offset) => offset,
+                None => {
+                    parser.pos = text.len();
+                    return Err(parser.error("no entry found"));
+                }
+            };
+            let start = parser.pos;
+            parser.pos += 1;
+
+            let kind = parser.identifier();
+            parser.skip_whitespace();
+            let close = match parser.peek() {
+                Some(b'{') => b'}',
+                Some(b'(') => b')',
+                // Not an entry, e.g. an email address in a comment
+                _ => continue,
+            };
+            parser.pos += 1;
+
+            match kind.to_ascii_lowercase().as_str() {
+                "" => return Err(parser.error("expected an entry type")),
+                "comment" | "preamble" | "string" => {
+                    let open = text.as_bytes()[parser.pos - 1];
+                    match closing_delimiter(text.as_bytes(), parser.pos, open, close) {
+                        Some(end) => parser.pos = end + 1,
+                        None => return Err(parser.error("unterminated entry")),
+                    }
+                    continue;
+                }
+                _ => {}
+            }
+
+            let mut entry = parser.entry_body(kind, close)?;
+            if entry.key.is_empty() {
+                return Err(ParseError {
+                    offset: start,
+                    message: "entry has no key",
+                });
+            }
+            entry.raw = Some(text[start..parser.pos].to_string());
+
+            return Ok(entry);
+        }
+    }
+
+    /// Parse the first entry in `text`, with the `@string` definitions before it and the entries
+    /// after it as its dependencies
+    ///
+    /// This reads entries taken from a database together with what they depend on, as
+    /// `source::Local` gives them.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use inspirer::bib::Entry;
+    ///
+    /// let text = "@string{prl = {Phys. Rev. Lett.}}\n\
+    ///             @article{Higgs:2014aqa, journal = prl, crossref = {Nobel}}\n\
+    ///             @proceedings{Nobel, year = 2014}\n";
+    /// let entry = Entry::parse_with_dependencies(text).unwrap();
+    ///
+    /// assert_eq!(entry.key(), "Higgs:2014aqa");
+    /// assert_eq!(
+    ///     entry.dependencies().strings,
+    ///     vec![("prl".to_string(), "@string{prl = {Phys. Rev. Lett.}}".to_string())]
+    /// );
+    /// assert_eq!(
+    ///     entry.dependencies().parents,
+    ///     vec![("Nobel".to_string(), "@proceedings{Nobel, year = 2014}".to_string())]
+    /// );
+    /// ```
+    pub fn parse_with_dependencies(text: &str) -> Result<Self, ParseError> {
+        let mut entry = Entry::parse(text)?;
+
+        let mut spans = entries(text).into_iter();
+        let start = spans.next().map_or(text.len(), |span| span.span.start);
+        entry.dependencies = Dependencies {
+            strings: string_spans(text)
+                .into_iter()
+                .filter(|string| string.span.end <= start)
+                .map(|string| (string.name, text[string.span].to_string()))
+                .collect(),
+            parents: spans
+                .map(|span| (span.key, text[span.span].to_string()))
+                .collect(),
+        };
+
+        Ok(entry)
+    }
+
+    /// The entry type, such as `article`, as it was written
+    pub fn kind(&self) -> &str {
+        &self.kind
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub fn fields(&self) -> &[Field] {
+        &self.fields
+    }
+
+    /// The value of a field, whose name is matched ignoring case as BibTeX does
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        self.fields
+            .iter()
+            .find(|f| f.name.eq_ignore_ascii_case(name))
+            .map(|f| &f.value)
+    }
+
+    /// Set the value of a field, adding it at the end if it is not there yet
+    pub fn set(&mut self, name: &str, value: Value) {
+        self.raw = None;
+        match self
+            .fields
+            .iter_mut()
+            .find(|f| f.name.eq_ignore_ascii_case(name))
+        {
+            Some(field) => field.value = value,
+            None => self.fields.push(Field {
+                name: name.to_string(),
+                value,
+            }),
+        }
+    }
+
+    /// Remove a field, returning its value
+    pub fn remove(&mut self, name: &str) -> Option<Value> {
+        let i = self
+            .fields
+            .iter()
+            .position(|f| f.name.eq_ignore_ascii_case(name))?;
+        self.raw = None;
+
+        Some(self.fields.remove(i).value)
+    }
+
+    /// The text of the entry as it was parsed, or `None` if it was built or has been modified
+    /// since
+    pub fn raw(&self) -> Option<&str> {
+        self.raw.as_deref()
+    }
+
+    /// The text the entry needs beside it, which is not part of `raw` or `Display`
+    pub fn dependencies(&self) -> &Dependencies {
+        &self.dependencies
+    }
+
+    /// The text of the entry if it is unmodified, and the serialised entry otherwise
+    ///
+    /// Either ends with a line break.
+    pub fn into_string(self) -> String {
+        match self.raw {
+            Some(raw) => raw + "\n",
+            None => self.to_string(),
+        }
+    }
+}
+
+/// The entry in a uniform layout, one field per line
+impl fmt::Display for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "@{}{{{}", self.kind, self.key)?;
+        for field in &self.fields {
+            write!(f, ",\n  {} = {}", field.name, field.value)?;
+        }
+        writeln!(f, "\n}}")
+    }
+}
+
+/// Why a BibTeX entry could not be parsed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    /// Byte offset in the text at which parsing failed
+    pub offset: usize,
+    pub message: &'static str,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at byte {}", self.message, self.offset)
+    }
+}
+
+impl error::Error for ParseError {}
+
+struct Parser<'a> {
+    text: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, message: &'static str) -> ParseError {
+        ParseError {
+            offset: self.pos,
+            message,
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.text.as_bytes().get(self.pos).cloned()
+    }
+
+    fn skip_whitespace(&mut self) {
+        self.pos = skip_whitespace(self.text.as_bytes(), self.pos);
+    }
+
+    /// Entry types, field names and abbreviations
+    fn identifier(&mut self) -> String {
+        let start = self.pos;
+        while let Some(b) = self.peek() {
+            if b.is_ascii_whitespace() || b"{}()\",=#%".contains(&b) {
+                break;
+            }
+            self.pos += 1;
+        }
+
+        self.text[start..self.pos].to_string()
+    }
+
+    /// The key and fields of an entry, up to and including the closing delimiter
+    fn entry_body(&mut self, kind: String, close: u8) -> Result<Entry, ParseError> {
+        let key_start = self.pos;
+        while let Some(b) = self.peek() {
+            if b == b',' || b == close {
+                break;
+            }
+            self.pos += 1;
+        }
+        let mut entry = Entry::new(&kind, self.text[key_start..self.pos].trim());
+
+        loop {
+            match self.peek() {
+                Some(b) if b == close => {
+                    self.pos += 1;
+                    return Ok(entry);
+                }
+                Some(b',') => self.pos += 1,
+                Some(_) => return Err(self.error("expected `,` or the end of the entry")),
+                None => return Err(self.error("unterminated entry")),
+            }
+
+            // A trailing comma is allowed
+            self.skip_whitespace();
+            if self.peek() == Some(close) {
+                continue;
+            }
+
+            let name = self.identifier();
+            if name.is_empty() {
+                return Err(self.error("expected a field name"));
+            }
+            self.skip_whitespace();
+            if self.peek() != Some(b'=') {
+                return Err(self.error("expected `=`"));
+            }
+            self.pos += 1;
+            let value = self.value()?;
+            entry.fields.push(Field { name, value });
+            self.skip_whitespace();
+        }
+    }
+
+    /// A value, possibly concatenated with `#`
+    fn value(&mut self) -> Result<Value, ParseError> {
+        let mut parts = Vec::new();
+        loop {
+            self.skip_whitespace();
+            parts.push(self.value_part()?);
+            self.skip_whitespace();
+            if self.peek() != Some(b'#') {
+                break;
+            }
+            self.pos += 1;
+        }
+
+        if parts.len() == 1 {
+            Ok(parts.remove(0))
+        } else {
+            Ok(Value::Concat(parts))
+        }
+    }
+
+    fn value_part(&mut self) -> Result<Value, ParseError> {
+        let bytes = self.text.as_bytes();
+        match self.peek() {
+            Some(b'{') => {
+                let start = self.pos + 1;
+                let end = closing_delimiter(bytes, start, b'{', b'}')
+                    .ok_or_else(|| self.error("unterminated `{`"))?;
+                self.pos = end + 1;
+                Ok(Value::Text(self.text[start..end].to_string()))
+            }
+            Some(b'"') => {
+                // Quotes inside braces do not end the text
+                let start = self.pos + 1;
+                let mut depth = 0;
+                for (i, &b) in bytes.iter().enumerate().skip(start) {
+                    match b {
+                        b'{' => depth += 1,
+                        b'}' if depth > 0 => depth -= 1,
+                        b'"' if depth == 0 => {
+                            self.pos = i + 1;
+                            return Ok(Value::Text(self.text[start..i].to_string()));
+                        }
+                        _ => {}
+                    }
+                }
+                Err(self.error("unterminated `\"`"))
+            }
+            Some(b) if b.is_ascii_digit() => {
+                let start = self.pos;
+                while self.peek().is_some_and(|b| b.is_ascii_digit()) {
+                    self.pos += 1;
+                }
+                Ok(Value::Number(self.text[start..self.pos].to_string()))
+            }
+            _ => {
+                let name = self.identifier();
+                if name.is_empty() {
+                    Err(self.error("expected a value"))
+                } else {
+                    Ok(Value::Macro(name))
+                }
+            }
+        }
+    }
+}
+
+/// An entry in a BibTeX database
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EntrySpan {
+    pub key: String,
+    /// Byte range of the entry, from the `@` to the closing delimiter
+    pub span: Range<usize>,
+}
+
+/// Find the entries with keys in a BibTeX database
+///
+/// `@string`, `@preamble` and `@comment` are skipped.  An unterminated entry at the end of the
+/// database is ignored.
+///
+/// # Examples
+///
+/// ```
+/// use inspirer::bib;
+///
+/// let database = "% My references\n@article{Higgs:2014aqa,\n  year = {2014}\n}\n@book{Weinberg, title = {QFT}}\n";
+/// let keys: Vec<String> = bib::entries(database).into_iter().map(|e| e.key).collect();
+///
+/// assert_eq!(keys, vec!["Higgs:2014aqa", "Weinberg"]);
+/// ```
+pub fn entries(text: &str) -> Vec<EntrySpan> {
+    let bytes = text.as_bytes();
+    let mut entries = Vec::new();
+
+    let mut pos = 0;
+    while let Some(offset) = text[pos..].find('@') {
+        let start = pos + offset;
+
+        // Entry type
+        let mut i = start + 1;
+        while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'_') {
+            i += 1;
+        }
+        let kind = text[start + 1..i].to_ascii_lowercase();
+        i = skip_whitespace(bytes, i);
+
+        let (open, close) = match bytes.get(i) {
+            Some(b'{') => (b'{', b'}'),
+            Some(b'(') => (b'(', b')'),
+            _ => {
+                // Not an entry, e.g. an email address in a comment
+                pos = start + 1;
+                continue;
+            }
+        };
+        let body = i + 1;
+
+        let end = match closing_delimiter(bytes, body, open, close) {
+            Some(end) => end,
+            None => break,
+        };
+        pos = end + 1;
+
+        if kind == "comment" || kind == "preamble" || kind == "string" {
+            continue;
+        }
+
+        let key_end = text[body..end].find(',').map_or(end, |k| body + k);
+        let key = text[body..key_end].trim();
+        if !key.is_empty() {
+            entries.push(EntrySpan {
+                key: key.to_string(),
+                span: start..end + 1,
+            });
+        }
+    }
+
+    entries
+}
+
+/// A `@string` definition in a BibTeX database
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StringSpan {
+    pub name: String,
+    /// The value as it was written, without expanding the abbreviations it uses
+    pub value: Value,
+    /// Byte range of the definition, from the `@` to the closing delimiter
+    pub span: Range<usize>,
+}
+
+/// Find the `@string` definitions in a BibTeX database, in order
+///
+/// Definitions which cannot be parsed are skipped.
+///
+/// # Examples
+///
+/// ```
+/// use inspirer::bib::{self, Value};
+///
+/// let database = "% Journals\n@string{prl = {Phys. Rev. Lett.}}\n@article{Higgs:2014aqa, journal = prl}\n";
+/// let strings = bib::string_spans(database);
+///
+/// assert_eq!(strings[0].name, "prl");
+/// assert_eq!(strings[0].value, Value::Text("Phys. Rev. Lett.".to_string()));
+/// assert_eq!(&database[strings[0].span.clone()], "@string{prl = {Phys. Rev. Lett.}}");
+/// ```
+pub fn string_spans(text: &str) -> Vec<StringSpan> {
+    let bytes = text.as_bytes();
+    let mut strings = Vec::new();
+
+    let mut pos = 0;
+    while let Some(offset) = text[pos..].find('@') {
+        let start = pos + offset;
+        let mut parser = Parser {
+            text,
+            pos: start + 1,
+        };
+        pos = parser.pos;
+
+        let kind = parser.identifier();
+        parser.skip_whitespace();
+        let (open, close) = match parser.peek() {
+            Some(b'{') => (b'{', b'}'),
+            Some(b'(') => (b'(', b')'),
+            // Not an entry, e.g. an email address in a comment
+            _ => continue,
+        };
+        parser.pos += 1;
+        let end = match closing_delimiter(bytes, parser.pos, open, close) {
+            Some(end) => end,
+            None => break,
+        };
+        pos = end + 1;
+
+        if !kind.eq_ignore_ascii_case("string") {
+            continue;
+        }
+        parser.skip_whitespace();
+        let name = parser.identifier();
+        parser.skip_whitespace();
+        if name.is_empty() || parser.peek() != Some(b'=') {
+            continue;
+        }
+        parser.pos += 1;
+        if let Ok(value) = parser.value() {
+            strings.push(StringSpan {
+                name,
+                value,
+                span: start..end + 1,
+            });
+        }
+    }
+
+    strings
+}
+
+/// The abbreviations defined with `@string` in a BibTeX database, in order
+///
+/// Each value is expanded with the abbreviations defined before it.  Definitions which cannot be
+/// parsed are skipped.
+///
+/// # Examples
+///
+/// ```
+/// use inspirer::bib::{self, Value};
+///
+/// let database = "@string{prl = {Phys. Rev.} # { Lett.}}\n@article{Higgs:2014aqa, journal = prl}\n";
+///
+/// assert_eq!(
+///     bib::strings(database),
+///     vec![("prl".to_string(), Value::Text("Phys. Rev. Lett.".to_string()))]
+/// );
+/// ```
+pub fn strings(text: &str) -> Vec<(String, Value)> {
+    let mut strings: Vec<(String, Value)> = Vec::new();
+    for StringSpan { name, value, .. } in string_spans(text) {
+        let value = value.expand(&strings);
+        strings.push((name, value));
+    }
+
+    strings
+}
+
+/// The key of the first entry in `text`, e.g. of a fetched entry
+pub fn key(text: &str) -> Option<String> {
+    entries(text).into_iter().next().map(|e| e.key)
+}
+
+fn skip_whitespace(bytes: &[u8], mut i: usize) -> usize {
+    while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+        i += 1;
+    }
+
+    i
+}
+
+/// Position of the delimiter which closes an entry whose body starts at `start`
+///
+/// Braces nest inside the body; a `)` only closes an entry opened with `(` outside braces.
+fn closing_delimiter(bytes: &[u8], start: usize, open: u8, close: u8) -> Option<usize> {
+    let mut depth = 0;
+    let mut parens = 0;
+
+    for (i, &b) in bytes.iter().enumerate().skip(start) {
+        match b {
+            b'{' => depth += 1,
+            b'}' if depth > 0 => depth -= 1,
+            b'}' if open == b'{' => return Some(i),
+            b'(' if depth == 0 && open == b'(' => parens += 1,
+            b')' if depth == 0 && close == b')' => {
+                if parens == 0 {
+                    return Some(i);
+                }
+                parens -= 1;
+            }
+            _ => {}
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INSPIRE: &str = r#"@article{Higgs:2014aqa,
+      author         = "Higgs, Peter W.",
+      title          = "{Nobel Lecture: Evading the Goldstone theorem}",
+      journal        = "Rev. Mod. Phys.",
+      volume         = "86",
+      year           = "2014",
+      SLACcitation   = "%%CITATION = RMPHA,86,851;%%"
+}
+"#;
+
+    const ADS: &str = r#"Query Results from the ADS Database
+
+@ARTICLE{1982PhRvL..48.1220A,
+   author = {{Albrecht}, A. and {Steinhardt}, P.~J.},
+    title = "{Cosmology for grand unified theories with radiatively induced symmetry breaking}",
+     year = 1982,
+    month = apr,
+     note = "Published in " # jan # { 1982},
+}
+"#;
+
+    #[test]
+    fn test_parse_inspire() {
+        let entry = Entry::parse(INSPIRE).unwrap();
+
+        assert_eq!(entry.kind(), "article");
+        assert_eq!(entry.key(), "Higgs:2014aqa");
+        assert_eq!(entry.fields().len(), 6);
+        assert_eq!(
+            entry.get("title"),
+            Some(&Value::Text(
+                "{Nobel Lecture: Evading the Goldstone theorem}".to_string()
+            ))
+        );
+        assert_eq!(
+            entry.get("slaccitation").and_then(Value::as_str),
+            Some("%%CITATION = RMPHA,86,851;%%")
+        );
+        assert_eq!(entry.raw(), Some(INSPIRE.trim_end()));
+    }
+
+    #[test]
+    fn test_parse_ads() {
+        let entry = Entry::parse(ADS).unwrap();
+
+        assert_eq!(entry.kind(), "ARTICLE");
+        assert_eq!(entry.key(), "1982PhRvL..48.1220A");
+        assert_eq!(
+            entry.get("author").and_then(Value::as_str),
+            Some("{Albrecht}, A. and {Steinhardt}, P.~J.")
+        );
+        assert!(entry
+            .raw()
+            .unwrap()
+            .starts_with("@ARTICLE{1982PhRvL..48.1220A,\n"));
+        assert!(entry.raw().unwrap().ends_with(",\n}"));
+        assert_eq!(entry.get("year"), Some(&Value::Number("1982".to_string())));
+        assert_eq!(entry.get("month"), Some(&Value::Macro("apr".to_string())));
+        assert_eq!(
+            entry.get("note"),
+            Some(&Value::Concat(vec![
+                Value::Text("Published in ".to_string()),
+                Value::Macro("jan".to_string()),
+                Value::Text(" 1982".to_string()),
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_parse_skips_non_entries() {
+        let entry =
+            Entry::parse("@string{prl = \"Phys. Rev. Lett.\"}\n@misc(Key, journal = prl)").unwrap();
+
+        assert_eq!(entry.key(), "Key");
+        assert_eq!(entry.get("journal"), Some(&Value::Macro("prl".to_string())));
+        assert_eq!(entry.raw(), Some("@misc(Key, journal = prl)"));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            Entry::parse("nothing here"),
+            Err(ParseError {
+                offset: 12,
+                message: "no entry found"
+            })
+        );
+        assert_eq!(
+            Entry::parse("@article{,}").unwrap_err().message,
+            "entry has no key"
+        );
+        assert_eq!(
+            Entry::parse("@article{Key, title = {Unbalanced}")
+                .unwrap_err()
+                .message,
+            "unterminated entry"
+        );
+        assert_eq!(
+            Entry::parse("@article{Key, title {A}}")
+                .unwrap_err()
+                .message,
+            "expected `=`"
+        );
+        assert_eq!(
+            Entry::parse("@article{Key, title = \"A}")
+                .unwrap_err()
+                .message,
+            "unterminated `\"`"
+        );
+    }
+
+    #[test]
+    fn test_serialize() {
+        let entry = Entry::parse(ADS).unwrap();
+        let text = entry.to_string();
+
+        assert_eq!(
+            text,
+            "@ARTICLE{1982PhRvL..48.1220A,
+  author = {{Albrecht}, A. and {Steinhardt}, P.~J.},
+  title = {{Cosmology for grand unified theories with radiatively induced symmetry breaking}},
+  year = 1982,
+  month = apr,
+  note = {Published in } # jan # { 1982}
+}
+"
+        );
+
+        // Serialising and parsing again gives the same fields
+        assert_eq!(Entry::parse(&text).unwrap().fields(), entry.fields());
+    }
+
+    #[test]
+    fn test_modify() {
+        let mut entry = Entry::parse(INSPIRE).unwrap();
+
+        entry.set("Year", Value::Text("2015".to_string()));
+        assert_eq!(entry.raw(), None);
+        assert_eq!(entry.get("year").and_then(Value::as_str), Some("2015"));
+        assert_eq!(entry.fields().len(), 6);
+
+        assert_eq!(
+            entry
+                .remove("slaccitation")
+                .and_then(|v| v.as_str().map(str::to_string)),
+            Some("%%CITATION = RMPHA,86,851;%%".to_string())
+        );
+        assert_eq!(entry.remove("slaccitation"), None);
+
+        entry.set("doi", Value::Text("10.1103/RevModPhys.86.851".to_string()));
+        assert_eq!(entry.fields().last().unwrap().name, "doi");
+        assert!(entry
+            .clone()
+            .into_string()
+            .contains("  doi = {10.1103/RevModPhys.86.851}\n}"));
+
+        let entry = Entry::new("misc", "Key");
+        assert_eq!(entry.into_string(), "@misc{Key\n}\n");
+    }
+
+    #[test]
+    fn test_entries() {
+        let database = "This is a comment, with an address: someone@example.com\n\
+                        @string{ prl = \"Phys. Rev. Lett.\" }\n\
+                        @Article{ Higgs:2014aqa ,\n  title = {Nobel {L}ecture},\n  journal = prl\n}\n\
+                        @comment{@article{Commented, }}\n\
+                        @misc(hep-th/9901001, note = {(see also)})\n\
+                        @preamble{\"\\newcommand{\\noop}[1]{}\"}\n\
+                        @article{Unterminated,\n  title = {";
+
+        let entries = entries(database);
+        assert_eq!(
+            entries
+                .iter()
+                .map(|e| e.key.as_str())
+                .collect::<Vec<&str>>(),
+            vec!["Higgs:2014aqa", "hep-th/9901001"]
+        );
+        assert_eq!(
+            &database[entries[0].span.clone()],
+            "@Article{ Higgs:2014aqa ,\n  title = {Nobel {L}ecture},\n  journal = prl\n}"
+        );
+        assert_eq!(
+            &database[entries[1].span.clone()],
+            "@misc(hep-th/9901001, note = {(see also)})"
+        );
+    }
+
+    #[test]
+    fn test_strings() {
+        let database = "@STRING(PRL = \"Phys. Rev. \" # \"Lett.\")\n\
+                        @comment{@string{ignored = {}}}\n\
+                        @string{broken}\n\
+                        @string{prlong = prl # { (Long)}}\n\
+                        @string{note = {Published } # apr}\n\
+                        @article{Higgs:2014aqa, journal = prl}\n";
+
+        let strings = strings(database);
+        assert_eq!(
+            strings,
+            vec![
+                (
+                    "PRL".to_string(),
+                    Value::Text("Phys. Rev. Lett.".to_string())
+                ),
+                (
+                    "prlong".to_string(),
+                    Value::Text("Phys. Rev. Lett. (Long)".to_string())
+                ),
+                (
+                    "note".to_string(),
+                    Value::Concat(vec![
+                        Value::Text("Published ".to_string()),
+                        Value::Macro("apr".to_string())
+                    ])
+                ),
+            ]
+        );
+
+        let prlong = &string_spans(database)[1];
+        assert_eq!(prlong.value.macros(), vec!["prl"]);
+        assert_eq!(
+            &database[prlong.span.clone()],
+            "@string{prlong = prl # { (Long)}}"
+        );
+
+        let entry = Entry::parse(ADS).unwrap();
+        assert_eq!(
+            entry.get("month").unwrap().expand(&strings),
+            Value::Macro("apr".to_string())
+        );
+        assert_eq!(
+            Value::Macro("prl".to_string()).expand(&strings),
+            Value::Text("Phys. Rev. Lett.".to_string())
+        );
+        assert_eq!(
+            Value::Concat(vec![
+                Value::Macro("Prl".to_string()),
+                Value::Number("116".to_string())
+            ])
+            .expand(&strings),
+            Value::Text("Phys. Rev. Lett.116".to_string())
+        );
+    }
+
+    #[test]
+    fn test_key() {
+        assert_eq!(
+            key("\n@article{Randall:1999ee,\n  title = {A}\n}\n"),
+            Some("Randall:1999ee".to_string())
+        );
+        assert_eq!(key("no entries here"), None);
+    }
+}
@@ -1,9 +1,8 @@
-#[macro_use(crate_version, crate_authors)]
-extern crate clap;
 use inspirer;
 
+use inspirer::cli;
 use inspirer::InspirerError;
-use std::error::Error;
+use std::path::Path;
 
 use human_panic::setup_panic;
 
@@ -12,72 +11,57 @@ extern crate slog;
 use slog::DrainExt;
 use slog_term;
 
-use clap::{App, Arg};
+use clap::Arg;
 
 fn main() {
     setup_panic!();
 
     // Initialize logging
     let drain = slog_term::streamer().stderr().build().fuse();
-    let root_logger = slog::Logger::root(drain, o!("version" => crate_version!()));
+    let root_logger = slog::Logger::root(drain, o!("version" => env!("CARGO_PKG_VERSION")));
 
-    if let Err(ref e) = run(&root_logger) {
-        match e {
-            _ => error!(root_logger, e.to_string();
-                    "error" => format!("{}", match e.source(){
-                        Some(e) => e.to_string(),
-                        None => String::new(),
-                    })
-            ),
-        }
-        ::std::process::exit(1);
-    }
+    cli::exit(&root_logger, run(&root_logger));
 }
 
-fn run(root_logger: &slog::Logger) -> Result<(), InspirerError> {
+/// Returns the exit status, which depends on how many keys could be resolved
+fn run(root_logger: &slog::Logger) -> Result<i32, InspirerError> {
     info!(root_logger, "Application started");
 
-    // Initialize instance of InspirerLib
-    let lib = inspirer::Inspirer::init(Some(root_logger.new(o!())));
-
     // Define CLI
-    let matches = App::new("aux2bib")
-        .version(crate_version!())
-        .author(crate_authors!(",\n"))
-        .about("gets BibTeX keys from Inspire")
-        .arg(
-            Arg::with_name("INPUT")
-                .help("Sets the file from which to extract BibTeX keys")
-                .index(1),
-        )
+    let matches = cli::app("aux2bib")
         .arg(
-            Arg::with_name("OUTPUT")
-                .help("Sets the file to which results should be appended")
-                .index(2),
+            Arg::with_name("missing-only")
+                .long("missing-only")
+                .requires("INPUT")
+                .help("Fetches only keys which are not in the databases named by \\bibdata"),
         )
         .get_matches();
 
-    // Get input from specified file or stdin
-    let input_data = lib.get_input(matches.value_of("INPUT"))?;
+    // Initialize instance of InspirerLib
+    let lib = cli::configure(&matches, root_logger)?;
 
     // Extract BibTeX tags from document
-    let keys = lib.aux2key(input_data);
-    info!(root_logger, "Extracted BibTeX keys";
-          "number_of_keys" => keys.len());
-
-    // Retrieve BibTeX entries from inspire.net
-    info!(root_logger, "Retrieving entries");
-    let mut bibtex_entries: Vec<String> = Vec::new();
-    for key in keys {
-        if let Some(bibtex_entry) = lib.bibtex(&key) {
-            bibtex_entries.push(bibtex_entry);
+    // Files included with \include have their own .aux files, which can only be found when reading
+    // from a file
+    let keys = match matches.value_of("INPUT") {
+        Some(file_name) => {
+            let origins = if matches.is_present("missing-only") {
+                lib.aux_file2missing(Path::new(file_name))?
+            } else {
+                lib.aux_file2key(Path::new(file_name))?
+            };
+
+            origins
+                .into_iter()
+                .map(|origin| {
+                    debug!(root_logger, "Found key";
+                           "key" => origin.key.as_str(), "file_name" => origin.file.display().to_string());
+                    origin.key
+                })
+                .collect()
         }
-    }
-
-    // Write BibTeX entries to file or stdout
-    lib.put_output(matches.value_of("OUTPUT"), bibtex_entries)?;
-
-    info!(root_logger, "Done");
+        None => lib.aux2key(lib.get_input(None)?),
+    };
 
-    Ok(())
+    cli::fetch(&lib, &matches, root_logger, &keys)
 }
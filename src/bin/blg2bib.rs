@@ -1,9 +1,8 @@
-#[macro_use(crate_version, crate_authors)]
-extern crate clap;
 use inspirer;
 
+use inspirer::blg::{Problem, Severity};
+use inspirer::cli;
 use inspirer::InspirerError;
-use std::error::Error;
 
 use human_panic::setup_panic;
 
@@ -12,72 +11,47 @@ extern crate slog;
 use slog::DrainExt;
 use slog_term;
 
-use clap::{App, Arg};
-
 fn main() {
     setup_panic!();
 
     // Initialize logging
     let drain = slog_term::streamer().stderr().build().fuse();
-    let root_logger = slog::Logger::root(drain, o!("version" => crate_version!()));
+    let root_logger = slog::Logger::root(drain, o!("version" => env!("CARGO_PKG_VERSION")));
 
-    if let Err(ref e) = run(&root_logger) {
-        match e {
-            _ => error!(root_logger, e.to_string();
-                    "error" => format!("{}", match e.source(){
-                        Some(e) => e.to_string(),
-                        None => String::new(),
-                    })
-            ),
-        }
-        ::std::process::exit(1);
-    }
+    cli::exit(&root_logger, run(&root_logger));
 }
 
-fn run(root_logger: &slog::Logger) -> Result<(), InspirerError> {
+/// Returns the exit status, which depends on how many keys could be resolved
+fn run(root_logger: &slog::Logger) -> Result<i32, InspirerError> {
     info!(root_logger, "Application started");
 
-    // Initialize instance of InspirerLib
-    let lib = inspirer::Inspirer::init(Some(root_logger.new(o!())));
-
     // Define CLI
-    let matches = App::new("blg2bib")
-        .version(crate_version!())
-        .author(crate_authors!(",\n"))
-        .about("gets BibTeX keys from Inspire")
-        .arg(
-            Arg::with_name("INPUT")
-                .help("Sets the file from which to extract BibTeX keys")
-                .index(1),
-        )
-        .arg(
-            Arg::with_name("OUTPUT")
-                .help("Sets the file to which results should be appended")
-                .index(2),
-        )
-        .get_matches();
+    let matches = cli::app("blg2bib").get_matches();
+
+    // Initialize instance of InspirerLib
+    let lib = cli::configure(&matches, root_logger)?;
 
     // Get input from specified file or stdin
     let input_data = lib.get_input(matches.value_of("INPUT"))?;
 
-    // Extract BibTeX tags from document
-    let keys = lib.blg2key(input_data);
-    info!(root_logger, "Extracted BibTeX keys";
-          "number_of_keys" => keys.len());
-
-    // Retrieve BibTeX entries from inspire.net
-    info!(root_logger, "Retrieving entries");
-    let mut bibtex_entries: Vec<String> = Vec::new();
-    for key in keys {
-        if let Some(bibtex_entry) = lib.bibtex(&key) {
-            bibtex_entries.push(bibtex_entry);
+    // Report problems other than missing entries, which are fetched below
+    let report = lib.blg2report(input_data);
+    for message in &report.messages {
+        if let Problem::MissingEntry { .. } = message.problem {
+            continue;
+        }
+        let line = message.line.to_string();
+        let section = message.section.map_or(String::new(), |s| s.to_string());
+        match message.severity {
+            Severity::Warning => warn!(root_logger, message.to_string();
+                                       "line" => line, "section" => section),
+            Severity::Error => error!(root_logger, message.to_string();
+                                      "line" => line, "section" => section),
         }
     }
 
-    // Write BibTeX entries to file or stdout
-    lib.put_output(matches.value_of("OUTPUT"), bibtex_entries)?;
-
-    info!(root_logger, "Done");
+    // Extract BibTeX tags from document
+    let keys = report.missing_keys();
 
-    Ok(())
+    cli::fetch(&lib, &matches, root_logger, &keys)
 }
@@ -0,0 +1,110 @@
+#[macro_use(crate_version, crate_authors, value_t)]
+extern crate clap;
+
+use inspirer::cache::Cache;
+use inspirer::InspirerError;
+use std::error::Error;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use human_panic::setup_panic;
+
+#[macro_use]
+extern crate slog;
+use slog::DrainExt;
+
+use clap::{App, AppSettings, Arg, SubCommand};
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+fn main() {
+    setup_panic!();
+
+    // Initialize logging
+    let drain = slog_term::streamer().stderr().build().fuse();
+    let root_logger = slog::Logger::root(drain, o!("version" => crate_version!()));
+
+    if let Err(ref e) = run(&root_logger) {
+        error!(root_logger, e.to_string();
+        "error" => match e.source() {
+            Some(e) => e.to_string(),
+            None => String::new(),
+        });
+        ::std::process::exit(1);
+    }
+}
+
+fn run(root_logger: &slog::Logger) -> Result<(), InspirerError> {
+    info!(root_logger, "Application started");
+
+    // Define CLI
+    let matches = App::new("inspirer-cache")
+        .version(crate_version!())
+        .author(crate_authors!(",\n"))
+        .about("manages the cache of BibTeX entries fetched by inspirer")
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .arg(
+            Arg::with_name("dir")
+                .long("dir")
+                .takes_value(true)
+                .value_name("DIR")
+                .help("Sets the cache directory instead of the user's cache directory"),
+        )
+        .arg(
+            Arg::with_name("ttl")
+                .long("ttl")
+                .takes_value(true)
+                .value_name("DAYS")
+                .help("Sets after how many days entries expire"),
+        )
+        .subcommand(SubCommand::with_name("list").about("lists cached entries"))
+        .subcommand(SubCommand::with_name("prune").about("removes expired entries"))
+        .subcommand(SubCommand::with_name("clear").about("removes all entries"))
+        .get_matches();
+
+    let dir = match matches.value_of("dir") {
+        Some(dir) => PathBuf::from(dir),
+        None => Cache::default_dir().ok_or_else(|| {
+            InspirerError::Io(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                "no cache directory",
+            ))
+        })?,
+    };
+    let mut cache = Cache::new(&dir);
+    if matches.is_present("ttl") {
+        let days = value_t!(matches, "ttl", u64).unwrap_or_else(|e| e.exit());
+        cache = cache.with_ttl(Duration::from_secs(days * SECONDS_PER_DAY));
+    }
+    info!(root_logger, "Using cache";
+          "dir" => dir.display().to_string());
+
+    match matches.subcommand_name() {
+        Some("list") => {
+            for entry in cache.list().map_err(InspirerError::Io)? {
+                println!(
+                    "{}\t{}\t{} days{}",
+                    entry.source,
+                    entry.id,
+                    entry.age.as_secs() / SECONDS_PER_DAY,
+                    if entry.expired { "\texpired" } else { "" }
+                );
+            }
+        }
+        Some("prune") => {
+            let removed = cache.prune().map_err(InspirerError::Io)?;
+            info!(root_logger, "Removed expired entries";
+                  "number_of_entries" => removed);
+        }
+        Some("clear") => {
+            let removed = cache.clear().map_err(InspirerError::Io)?;
+            info!(root_logger, "Removed all entries";
+                  "number_of_entries" => removed);
+        }
+        _ => unreachable!("clap requires a subcommand"),
+    }
+
+    info!(root_logger, "Done");
+
+    Ok(())
+}
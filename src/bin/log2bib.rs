@@ -0,0 +1,40 @@
+use inspirer;
+
+use inspirer::cli;
+use inspirer::InspirerError;
+
+use human_panic::setup_panic;
+
+#[macro_use]
+extern crate slog;
+use slog::DrainExt;
+use slog_term;
+
+fn main() {
+    setup_panic!();
+
+    // Initialize logging
+    let drain = slog_term::streamer().stderr().build().fuse();
+    let root_logger = slog::Logger::root(drain, o!("version" => env!("CARGO_PKG_VERSION")));
+
+    cli::exit(&root_logger, run(&root_logger));
+}
+
+/// Returns the exit status, which depends on how many keys could be resolved
+fn run(root_logger: &slog::Logger) -> Result<i32, InspirerError> {
+    info!(root_logger, "Application started");
+
+    // Define CLI
+    let matches = cli::app("log2bib").get_matches();
+
+    // Initialize instance of InspirerLib
+    let lib = cli::configure(&matches, root_logger)?;
+
+    // Get input from specified file or stdin
+    let input_data = lib.get_input(matches.value_of("INPUT"))?;
+
+    // Extract BibTeX tags from document
+    let keys = lib.log2key(input_data);
+
+    cli::fetch(&lib, &matches, root_logger, &keys)
+}
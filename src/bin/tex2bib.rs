@@ -0,0 +1,50 @@
+use inspirer;
+
+use inspirer::cli;
+use inspirer::InspirerError;
+use std::path::Path;
+
+use human_panic::setup_panic;
+
+#[macro_use]
+extern crate slog;
+use slog::DrainExt;
+use slog_term;
+
+fn main() {
+    setup_panic!();
+
+    // Initialize logging
+    let drain = slog_term::streamer().stderr().build().fuse();
+    let root_logger = slog::Logger::root(drain, o!("version" => env!("CARGO_PKG_VERSION")));
+
+    cli::exit(&root_logger, run(&root_logger));
+}
+
+/// Returns the exit status, which depends on how many keys could be resolved
+fn run(root_logger: &slog::Logger) -> Result<i32, InspirerError> {
+    info!(root_logger, "Application started");
+
+    // Define CLI
+    let matches = cli::app("tex2bib").get_matches();
+
+    // Initialize instance of InspirerLib
+    let lib = cli::configure(&matches, root_logger)?;
+
+    // Extract BibTeX tags from document
+    // Files included with \input or \include can only be found when reading from a file
+    let keys = match matches.value_of("INPUT") {
+        Some(file_name) => lib
+            .tex_file2key(Path::new(file_name))?
+            .into_iter()
+            .map(|origin| {
+                debug!(root_logger, "Found key";
+                       "key" => origin.key.as_str(), "file_name" => origin.file.display().to_string());
+                origin.key
+            })
+            .collect(),
+        None => lib.tex2key(lib.get_input(None)?),
+    };
+
+    cli::fetch(&lib, &matches, root_logger, &keys)
+}
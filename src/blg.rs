@@ -0,0 +1,494 @@
+//! Parsing BibTeX and biber logs
+//!
+//! Besides the entries which could not be found, the logs explain most other reasons for a
+//! broken bibliography: missing database files, duplicate keys, missing fields and unresolved
+//! cross-references.  Every warning and error is collected into a
+//! [`BlgReport`](struct.BlgReport.html).
+
+use regex::Regex;
+
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+/// What a log message is about
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Problem {
+    /// A cited key is not in any database
+    MissingEntry { key: String },
+    /// A database file could not be opened
+    MissingDatabase { file: String },
+    /// A key is defined more than once; BibTeX does not always say which
+    DuplicateEntry { key: Option<String> },
+    /// An entry lacks a field required by its type
+    MissingField { key: String, field: String },
+    /// The cross-reference of an entry could not be resolved
+    Crossref { key: String, crossref: String },
+    /// Any other warning or error
+    Other,
+}
+
+/// A warning or error from a BibTeX or biber log
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Message {
+    pub problem: Problem,
+    pub severity: Severity,
+    /// The message as written, without biber's prefix or BibTeX's `Warning--`
+    pub text: String,
+    /// Line of the log on which the message starts, starting from 1
+    pub line: usize,
+    /// The biber refsection, or `None` for BibTeX
+    pub section: Option<u32>,
+}
+
+/// Explain the problem in words
+impl fmt::Display for Message {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.problem {
+            Problem::MissingEntry { ref key } => write!(f, "no database entry for '{}'", key),
+            Problem::MissingDatabase { ref file } => {
+                write!(f, "database file '{}' could not be opened", file)
+            }
+            Problem::DuplicateEntry { key: Some(ref key) } => {
+                write!(f, "'{}' is defined more than once", key)
+            }
+            Problem::DuplicateEntry { key: None } => {
+                write!(f, "an entry is defined more than once")
+            }
+            Problem::MissingField { ref key, ref field } => {
+                write!(f, "'{}' lacks the {} field", key, field)
+            }
+            Problem::Crossref {
+                ref key,
+                ref crossref,
+            } => write!(
+                f,
+                "the cross-reference from '{}' to '{}' could not be resolved",
+                key, crossref
+            ),
+            Problem::Other => write!(f, "{}", self.text),
+        }
+    }
+}
+
+/// The warnings and errors of a BibTeX or biber log
+///
+/// # Examples
+///
+/// ```
+/// use inspirer::blg::{BlgReport, Problem};
+///
+/// let report = BlgReport::parse(
+/// r"[48] Biber.pm:359> INFO - Reading 'test_biber.bcf'
+/// [150] Biber.pm:3670> INFO - Processing section 0
+/// [165] Biber.pm:3840> ERROR - Cannot find 'refs.bib'!
+/// [170] Utils.pm:164> WARN - I didn't find a database entry for 'Guth:1980zm' (section 0)");
+///
+/// assert_eq!(
+///     report.messages[0].problem,
+///     Problem::MissingDatabase { file: "refs.bib".to_string() }
+/// );
+/// assert_eq!(report.messages[1].line, 4);
+/// assert_eq!(report.messages[1].section, Some(0));
+/// assert_eq!(report.missing_keys(), vec!["Guth:1980zm"]);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BlgReport {
+    /// Messages in the order they appear
+    pub messages: Vec<Message>,
+}
+
+impl BlgReport {
+    /// Parse a BibTeX or biber log
+    ///
+    /// Parsing never fails; lines which are neither warnings nor errors are skipped.
+    pub fn parse(input: &str) -> Self {
+        lazy_static! {
+            static ref BIBER_REGEX: Regex =
+                Regex::new(r"^\[[[:digit:]]+\] [^>]*> (?P<level>INFO|WARN|ERROR) - (?P<text>.*)$")
+                    .expect("blg regex compiled during development");
+            static ref PROCESSING_REGEX: Regex =
+                Regex::new(r"^Processing section (?P<section>[[:digit:]]+)")
+                    .expect("blg regex compiled during development");
+            static ref SECTION_REGEX: Regex = Regex::new(r"\(section (?P<section>[[:digit:]]+)\)")
+                .expect("blg regex compiled during development");
+        }
+
+        let lines: Vec<&str> = input.lines().collect();
+        let mut messages = Vec::new();
+        let mut current_section: Option<u32> = None;
+
+        for (i, line) in lines.iter().enumerate() {
+            let next = lines.get(i + 1).cloned().unwrap_or("");
+
+            if let Some(c) = BIBER_REGEX.captures(line) {
+                let text = c.name("text").map_or("", |m| m.as_str());
+                let severity = match &c["level"] {
+                    "WARN" => Severity::Warning,
+                    "ERROR" => Severity::Error,
+                    _ => {
+                        if let Some(s) = PROCESSING_REGEX.captures(text) {
+                            current_section = s["section"].parse().ok();
+                        }
+                        continue;
+                    }
+                };
+                let section = SECTION_REGEX
+                    .captures(text)
+                    .and_then(|s| s["section"].parse().ok())
+                    .or(current_section);
+
+                messages.push(Message {
+                    problem: biber_problem(text),
+                    severity,
+                    text: text.to_string(),
+                    line: i + 1,
+                    section,
+                });
+            } else if let Some((problem, severity)) = bibtex_problem(line, next) {
+                messages.push(Message {
+                    problem,
+                    severity,
+                    text: line.trim_start_matches("Warning--").to_string(),
+                    line: i + 1,
+                    section: None,
+                });
+            }
+        }
+
+        BlgReport { messages }
+    }
+
+    /// Keys without a database entry, sorted alphabetically and without duplicates
+    pub fn missing_keys(&self) -> Vec<String> {
+        let mut keys: Vec<String> = self
+            .messages
+            .iter()
+            .filter_map(|m| match m.problem {
+                Problem::MissingEntry { ref key } => Some(key.clone()),
+                _ => None,
+            })
+            .collect();
+
+        keys.sort_unstable();
+        keys.dedup();
+
+        keys
+    }
+
+    /// Messages which are errors rather than warnings
+    pub fn errors(&self) -> impl Iterator<Item = &Message> {
+        self.messages
+            .iter()
+            .filter(|m| m.severity == Severity::Error)
+    }
+}
+
+/// Classify the text of a biber warning or error
+fn biber_problem(text: &str) -> Problem {
+    lazy_static! {
+        static ref CROSSREF_REGEX: Regex = Regex::new(
+            r"^I didn't find a database entry for crossref '(?P<crossref>[^']+)' in entry '(?P<key>[^']+)'"
+        )
+        .expect("blg regex compiled during development");
+        static ref MISSING_ENTRY_REGEX: Regex =
+            Regex::new(r"^I didn't find a database entry for '(?P<key>[^']+)'")
+                .expect("blg regex compiled during development");
+        static ref MISSING_DATABASE_REGEX: Regex =
+            Regex::new(r"^Cannot find (?:file )?'(?P<file>[^']+)'")
+                .expect("blg regex compiled during development");
+        static ref DUPLICATE_REGEX: Regex = Regex::new(r"^Duplicate entry key '(?P<key>[^']+)'")
+            .expect("blg regex compiled during development");
+        static ref MISSING_FIELD_REGEX: Regex = Regex::new(
+            r"Entry '(?P<key>[^']+)'.*: Missing mandatory field '(?P<field>[^']+)'"
+        )
+        .expect("blg regex compiled during development");
+    }
+
+    if let Some(c) = CROSSREF_REGEX.captures(text) {
+        Problem::Crossref {
+            key: c["key"].to_string(),
+            crossref: c["crossref"].to_string(),
+        }
+    } else if let Some(c) = MISSING_ENTRY_REGEX.captures(text) {
+        Problem::MissingEntry {
+            key: c["key"].to_string(),
+        }
+    } else if let Some(c) = MISSING_DATABASE_REGEX.captures(text) {
+        Problem::MissingDatabase {
+            file: c["file"].to_string(),
+        }
+    } else if let Some(c) = DUPLICATE_REGEX.captures(text) {
+        Problem::DuplicateEntry {
+            key: Some(c["key"].to_string()),
+        }
+    } else if let Some(c) = MISSING_FIELD_REGEX.captures(text) {
+        Problem::MissingField {
+            key: c["key"].to_string(),
+            field: c["field"].to_string(),
+        }
+    } else {
+        Problem::Other
+    }
+}
+
+/// Classify a line of a BibTeX log
+///
+/// BibTeX spreads some messages over several lines, so the following line is needed too.
+/// Returns `None` for lines which do not start a warning or error.
+fn bibtex_problem(line: &str, next: &str) -> Option<(Problem, Severity)> {
+    lazy_static! {
+        static ref MISSING_ENTRY_REGEX: Regex =
+            Regex::new(r#"^Warning--I didn't find a database entry for "(?P<key>[^"]+)""#)
+                .expect("blg regex compiled during development");
+        static ref MISSING_FIELD_REGEX: Regex =
+            Regex::new(r"^Warning--empty (?P<field>.+) in (?P<key>\S+)$")
+                .expect("blg regex compiled during development");
+        static ref CROSSREF_REGEX: Regex = Regex::new(
+            r#"^(?:A bad cross reference-|Warning--you've nested cross references)--entry "(?P<key>[^"]+)""#
+        )
+        .expect("blg regex compiled during development");
+        static ref REFERS_TO_REGEX: Regex = Regex::new(r#"^refers to entry "(?P<crossref>[^"]+)""#)
+            .expect("blg regex compiled during development");
+        static ref MISSING_DATABASE_REGEX: Regex =
+            Regex::new(r"^I couldn't open database file (?P<file>\S+)")
+                .expect("blg regex compiled during development");
+        static ref REPEATED_ENTRY_KEY_REGEX: Regex =
+            Regex::new(r"^ : @[[:alpha:]]+\s*[{(]\s*(?P<key>[^,\s]+)")
+                .expect("blg regex compiled during development");
+    }
+
+    // Errors give their location as "---line 3 of file test.aux", either at the end of the
+    // message or on the following line.  Bad cross-references are the exception.
+    let is_error = (!line.starts_with("---")
+        && (line.contains("---line ") || line.contains("---while reading file")))
+        || next.starts_with("---line ")
+        || next.starts_with("---while reading file")
+        || line.starts_with("A bad cross reference");
+    let severity = if line.starts_with("Warning--") {
+        Severity::Warning
+    } else if is_error {
+        Severity::Error
+    } else {
+        return None;
+    };
+
+    let problem = if let Some(c) = MISSING_ENTRY_REGEX.captures(line) {
+        Problem::MissingEntry {
+            key: c["key"].to_string(),
+        }
+    } else if let Some(c) = MISSING_FIELD_REGEX.captures(line) {
+        Problem::MissingField {
+            key: c["key"].to_string(),
+            field: c["field"].to_string(),
+        }
+    } else if let Some(c) = CROSSREF_REGEX.captures(line) {
+        Problem::Crossref {
+            key: c["key"].to_string(),
+            crossref: REFERS_TO_REGEX
+                .captures(next)
+                .map(|r| r["crossref"].to_string())
+                .unwrap_or_default(),
+        }
+    } else if let Some(c) = MISSING_DATABASE_REGEX.captures(line) {
+        Problem::MissingDatabase {
+            file: c["file"].to_string(),
+        }
+    } else if line.starts_with("Repeated entry") {
+        Problem::DuplicateEntry {
+            key: REPEATED_ENTRY_KEY_REGEX
+                .captures(next)
+                .map(|c| c["key"].to_string()),
+        }
+    } else {
+        Problem::Other
+    };
+
+    Some((problem, severity))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn problems(input: &str) -> Vec<Problem> {
+        BlgReport::parse(input)
+            .messages
+            .into_iter()
+            .map(|m| m.problem)
+            .collect()
+    }
+
+    #[test]
+    fn test_example_bibtex() {
+        let report = BlgReport::parse(include_str!("../example_files/test_bibtex.blg"));
+
+        assert_eq!(report.messages.len(), 2);
+        assert_eq!(report.messages[0].line, 6);
+        assert_eq!(report.messages[0].severity, Severity::Warning);
+        assert_eq!(report.messages[0].section, None);
+        assert_eq!(
+            report.missing_keys(),
+            vec!["Higgs:2014aqa", "Higgs:2015mei"]
+        );
+    }
+
+    #[test]
+    fn test_example_biber() {
+        let report = BlgReport::parse(include_str!("../example_files/test_biber.blg"));
+
+        assert_eq!(report.messages.len(), 4);
+        assert!(report.messages.iter().all(|m| m.section == Some(0)));
+        assert_eq!(
+            report.missing_keys(),
+            vec![
+                "1982PhRvL..48.1220A",
+                "Albrecht:1982wi",
+                "Guth:1980zm",
+                "Linde:1981mu"
+            ]
+        );
+        assert_eq!(report.errors().count(), 0);
+    }
+
+    #[test]
+    fn test_bibtex_problems() {
+        let report = BlgReport::parse(
+            r#"Database file #1: refs.bib
+I couldn't open database file missing.bib
+---line 4 of file test.aux
+ : \bibdata{refs,missing
+ :                      }
+I'm skipping whatever remains of this command
+Repeated entry---line 17 of file refs.bib
+ : @article{Higgs:2014aqa
+ :                       ,
+I'm skipping whatever remains of this entry
+Warning--empty journal in Higgs:2015mei
+Warning--you've nested cross references--entry "Guth:1980zm"
+refers to entry "Proceedings:1980", which also refers to something
+A bad cross reference---entry "Linde:1981mu"
+refers to entry "Proceedings:1981", which doesn't exist
+I was expecting a `,' or a `}'---line 25 of file refs.bib
+Warning--I didn't find a database entry for "Higgs:2015mei"
+(There were 3 error messages)"#,
+        );
+
+        assert_eq!(
+            report
+                .messages
+                .iter()
+                .map(|m| (m.line, m.severity))
+                .collect::<Vec<(usize, Severity)>>(),
+            vec![
+                (2, Severity::Error),
+                (7, Severity::Error),
+                (11, Severity::Warning),
+                (12, Severity::Warning),
+                (14, Severity::Error),
+                (16, Severity::Error),
+                (17, Severity::Warning),
+            ]
+        );
+        assert_eq!(
+            report
+                .messages
+                .into_iter()
+                .map(|m| m.problem)
+                .collect::<Vec<Problem>>(),
+            vec![
+                Problem::MissingDatabase {
+                    file: "missing.bib".to_string()
+                },
+                Problem::DuplicateEntry {
+                    key: Some("Higgs:2014aqa".to_string())
+                },
+                Problem::MissingField {
+                    key: "Higgs:2015mei".to_string(),
+                    field: "journal".to_string()
+                },
+                Problem::Crossref {
+                    key: "Guth:1980zm".to_string(),
+                    crossref: "Proceedings:1980".to_string()
+                },
+                Problem::Crossref {
+                    key: "Linde:1981mu".to_string(),
+                    crossref: "Proceedings:1981".to_string()
+                },
+                Problem::Other,
+                Problem::MissingEntry {
+                    key: "Higgs:2015mei".to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_biber_problems() {
+        assert_eq!(
+            problems(
+                r"[150] Biber.pm:3670> INFO - Processing section 0
+[164] Biber.pm:3840> ERROR - Cannot find 'missing.bib'!
+[170] bibtex.pm:1511> WARN - Duplicate entry key 'Higgs:2014aqa' in file 'refs.bib', skipping ...
+[171] Biber.pm:2036> WARN - Datamodel: Entry 'Higgs:2015mei' (refs.bib): Missing mandatory field 'journaltitle'
+[172] Utils.pm:164> WARN - I didn't find a database entry for crossref 'Proceedings:1980' in entry 'Guth:1980zm' - ignoring (section 0)
+[173] Utils.pm:164> WARN - BibTeX subsystem: refs.bib, line 20, syntax error: found end of entry"
+            ),
+            vec![
+                Problem::MissingDatabase {
+                    file: "missing.bib".to_string()
+                },
+                Problem::DuplicateEntry {
+                    key: Some("Higgs:2014aqa".to_string())
+                },
+                Problem::MissingField {
+                    key: "Higgs:2015mei".to_string(),
+                    field: "journaltitle".to_string()
+                },
+                Problem::Crossref {
+                    key: "Guth:1980zm".to_string(),
+                    crossref: "Proceedings:1980".to_string()
+                },
+                Problem::Other,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_biber_sections() {
+        let report = BlgReport::parse(
+            r"[150] Biber.pm:3670> INFO - Processing section 1
+[170] Utils.pm:164> WARN - I didn't find a database entry for 'Guth:1980zm' (section 2)
+[171] Biber.pm:3840> ERROR - Cannot find 'appendix.bib'!",
+        );
+
+        assert_eq!(
+            report
+                .messages
+                .iter()
+                .map(|m| m.section)
+                .collect::<Vec<Option<u32>>>(),
+            vec![Some(2), Some(1)]
+        );
+    }
+
+    #[test]
+    fn test_explanation() {
+        let report = BlgReport::parse(
+            "Warning--empty journal in Higgs:2015mei\nWarning--can't use both author and editor fields in Higgs:2014aqa",
+        );
+
+        assert_eq!(
+            report.messages[0].to_string(),
+            "'Higgs:2015mei' lacks the journal field"
+        );
+        assert_eq!(
+            report.messages[1].to_string(),
+            "can't use both author and editor fields in Higgs:2014aqa"
+        );
+    }
+}
@@ -0,0 +1,338 @@
+//! On-disk cache of fetched BibTeX entries
+//!
+//! Each entry is stored in its own file, `<dir>/<source>/<identifier>.bib`, so that the cache can
+//! be inspected and cleaned by hand.  The modification time of the file records when the entry
+//! was fetched; entries older than the cache's time to live are treated as missing.
+
+use std::ffi::OsStr;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::{Duration, SystemTime};
+
+/// Time to live of cached entries unless set with `Cache::with_ttl`: 30 days
+pub const DEFAULT_TTL: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// A cached entry, as listed by `Cache::list`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheEntry {
+    /// The source the entry was fetched from, such as "inspire"
+    pub source: String,
+    /// The identifier of the entry within its source, usually its BibTeX key
+    pub id: String,
+    pub path: PathBuf,
+    /// Time since the entry was fetched
+    pub age: Duration,
+    /// Whether the entry is older than the cache's time to live
+    pub expired: bool,
+}
+
+/// A directory of cached BibTeX entries
+///
+/// # Examples
+///
+/// ```
+/// use inspirer::cache::Cache;
+///
+/// let dir = assert_fs::TempDir::new().unwrap();
+/// let cache = Cache::new(dir.path());
+///
+/// cache.put("inspire", "Randall:1999ee", "@article{Randall:1999ee,\n}\n").unwrap();
+/// assert_eq!(
+///     cache.get("inspire", "Randall:1999ee"),
+///     Some("@article{Randall:1999ee,\n}\n".to_string())
+/// );
+/// assert_eq!(cache.get("ads", "Randall:1999ee"), None);
+///
+/// cache.clear().unwrap();
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cache {
+    dir: PathBuf,
+    ttl: Duration,
+}
+
+impl Cache {
+    /// Use `dir` as the cache, with the default time to live
+    ///
+    /// The directory is created when the first entry is stored.
+    pub fn new<P: AsRef<Path>>(dir: P) -> Self {
+        Cache {
+            dir: dir.as_ref().to_path_buf(),
+            ttl: DEFAULT_TTL,
+        }
+    }
+
+    /// The user's cache directory for inspirer
+    ///
+    /// This is `$XDG_CACHE_HOME/inspirer` (by default `~/.cache/inspirer`) on Linux, and the
+    /// platform's equivalent elsewhere.  Returns `None` if there is no such directory, e.g.
+    /// because `HOME` is not set.
+    pub fn default_dir() -> Option<PathBuf> {
+        dirs::cache_dir().map(|dir| dir.join("inspirer"))
+    }
+
+    /// Set how long entries are used for after they have been fetched
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// Look up an entry
+    ///
+    /// Returns `None` if the entry is not cached, cannot be read, or has expired.
+    pub fn get(&self, source: &str, id: &str) -> Option<String> {
+        let path = self.path(source, id);
+        let age = age(&path).ok()?;
+        if age > self.ttl {
+            return None;
+        }
+
+        fs::read_to_string(path).ok()
+    }
+
+    /// Store an entry, replacing any cached version
+    pub fn put(&self, source: &str, id: &str, entry: &str) -> io::Result<()> {
+        let path = self.path(source, id);
+        fs::create_dir_all(self.dir.join(escape(source)))?;
+
+        // Write to a temporary file first so that readers never see half an entry
+        let mut tmp_name = path.as_os_str().to_os_string();
+        tmp_name.push(format!(".{}.tmp", process::id()));
+        let tmp_path = PathBuf::from(tmp_name);
+        fs::write(&tmp_path, entry)?;
+        fs::rename(&tmp_path, &path)
+    }
+
+    /// All cached entries, sorted by source and identifier
+    ///
+    /// A cache directory which does not exist yet is empty.
+    pub fn list(&self) -> io::Result<Vec<CacheEntry>> {
+        let mut entries = Vec::new();
+
+        let sources = match fs::read_dir(&self.dir) {
+            Ok(sources) => sources,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(entries),
+            Err(e) => return Err(e),
+        };
+        for source in sources {
+            let source = source?;
+            if !source.file_type()?.is_dir() {
+                continue;
+            }
+            let source_name = unescape(&source.file_name().to_string_lossy());
+
+            for file in fs::read_dir(source.path())? {
+                let path = file?.path();
+                if path.extension() != Some(OsStr::new("bib")) {
+                    continue;
+                }
+                let id = match path.file_stem() {
+                    Some(stem) => unescape(&stem.to_string_lossy()),
+                    None => continue,
+                };
+                let age = age(&path)?;
+
+                entries.push(CacheEntry {
+                    source: source_name.clone(),
+                    id,
+                    path,
+                    age,
+                    expired: age > self.ttl,
+                });
+            }
+        }
+
+        entries.sort_unstable_by(|a, b| (&a.source, &a.id).cmp(&(&b.source, &b.id)));
+
+        Ok(entries)
+    }
+
+    /// Remove expired entries, returning how many were removed
+    pub fn prune(&self) -> io::Result<usize> {
+        let expired: Vec<CacheEntry> = self.list()?.into_iter().filter(|e| e.expired).collect();
+        for entry in &expired {
+            fs::remove_file(&entry.path)?;
+        }
+
+        Ok(expired.len())
+    }
+
+    /// Remove all entries, returning how many were removed
+    pub fn clear(&self) -> io::Result<usize> {
+        let entries = self.list()?;
+        for entry in &entries {
+            fs::remove_file(&entry.path)?;
+        }
+
+        Ok(entries.len())
+    }
+
+    fn path(&self, source: &str, id: &str) -> PathBuf {
+        self.dir
+            .join(escape(source))
+            .join(format!("{}.bib", escape(id)))
+    }
+}
+
+/// Time since a file was last modified
+fn age(path: &Path) -> io::Result<Duration> {
+    let modified = fs::metadata(path)?.modified()?;
+
+    // A modification time in the future counts as new
+    Ok(SystemTime::now()
+        .duration_since(modified)
+        .unwrap_or_else(|_| Duration::from_secs(0)))
+}
+
+/// Turn an identifier into a file name which is valid on all platforms
+///
+/// Anything but ASCII letters, digits, `-`, `_` and `.` is written as `%XX`, so arXiv identifiers
+/// such as `hep-th/9901001` and the `:` of INSPIRE keys are safe.
+fn escape(id: &str) -> String {
+    let mut escaped = String::with_capacity(id.len());
+    for b in id.bytes() {
+        match b {
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'-' | b'_' | b'.' => escaped.push(b as char),
+            _ => escaped.push_str(&format!("%{:02X}", b)),
+        }
+    }
+
+    escaped
+}
+
+/// Undo `escape`
+fn unescape(name: &str) -> String {
+    let bytes = name.as_bytes();
+    let mut unescaped = Vec::with_capacity(bytes.len());
+
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            if let Some(b) = name
+                .get(i + 1..i + 3)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            {
+                unescaped.push(b);
+                i += 3;
+                continue;
+            }
+        }
+        unescaped.push(bytes[i]);
+        i += 1;
+    }
+
+    String::from_utf8_lossy(&unescaped).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ENTRY: &str = "@article{Randall:1999ee,\n      title          = \"{A Large mass hierarchy from a small extra dimension}\"\n}\n";
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape("Randall:1999ee"), "Randall%3A1999ee");
+        assert_eq!(escape("hep-th/9901001"), "hep-th%2F9901001");
+        assert_eq!(escape("2003A&A...400..1R"), "2003A%26A...400..1R");
+
+        for id in &[
+            "Randall:1999ee",
+            "hep-th/9901001",
+            "2003A&A...400..1R",
+            "50%",
+        ] {
+            assert_eq!(unescape(&escape(id)), *id);
+        }
+    }
+
+    #[test]
+    fn test_put_get() {
+        let tmp_dir = assert_fs::TempDir::new().expect("can create tmp_dir");
+        let cache = Cache::new(tmp_dir.path().join("cache"));
+
+        assert_eq!(cache.get("inspire", "Randall:1999ee"), None);
+
+        cache.put("inspire", "Randall:1999ee", ENTRY).unwrap();
+        cache.put("arxiv", "hep-th/9901001", ENTRY).unwrap();
+
+        assert_eq!(
+            cache.get("inspire", "Randall:1999ee"),
+            Some(ENTRY.to_string())
+        );
+        assert_eq!(
+            cache.get("arxiv", "hep-th/9901001"),
+            Some(ENTRY.to_string())
+        );
+        assert_eq!(cache.get("ads", "Randall:1999ee"), None);
+    }
+
+    #[test]
+    fn test_ttl() {
+        let tmp_dir = assert_fs::TempDir::new().expect("can create tmp_dir");
+        let cache = Cache::new(tmp_dir.path()).with_ttl(Duration::from_secs(0));
+
+        cache.put("inspire", "Randall:1999ee", ENTRY).unwrap();
+        std::thread::sleep(Duration::from_millis(10));
+
+        assert_eq!(cache.get("inspire", "Randall:1999ee"), None);
+        assert!(cache.list().unwrap()[0].expired);
+
+        let cache = cache.with_ttl(DEFAULT_TTL);
+        assert_eq!(
+            cache.get("inspire", "Randall:1999ee"),
+            Some(ENTRY.to_string())
+        );
+    }
+
+    #[test]
+    fn test_list_prune_clear() {
+        let tmp_dir = assert_fs::TempDir::new().expect("can create tmp_dir");
+        let cache = Cache::new(tmp_dir.path().join("cache"));
+
+        assert!(cache.list().unwrap().is_empty());
+
+        cache.put("inspire", "Randall:1999ee", ENTRY).unwrap();
+        cache.put("arxiv", "hep-th/9901001", ENTRY).unwrap();
+        cache.put("ads", "1999PhRvL..83.3370R", ENTRY).unwrap();
+
+        let entries = cache.list().unwrap();
+        assert_eq!(
+            entries
+                .iter()
+                .map(|e| (e.source.as_str(), e.id.as_str(), e.expired))
+                .collect::<Vec<(&str, &str, bool)>>(),
+            vec![
+                ("ads", "1999PhRvL..83.3370R", false),
+                ("arxiv", "hep-th/9901001", false),
+                ("inspire", "Randall:1999ee", false),
+            ]
+        );
+
+        assert_eq!(cache.prune().unwrap(), 0);
+        std::thread::sleep(Duration::from_millis(10));
+        assert_eq!(
+            cache
+                .clone()
+                .with_ttl(Duration::from_secs(0))
+                .prune()
+                .unwrap(),
+            3
+        );
+        assert!(cache.list().unwrap().is_empty());
+
+        cache.put("inspire", "Randall:1999ee", ENTRY).unwrap();
+        assert_eq!(cache.clear().unwrap(), 1);
+        assert_eq!(cache.get("inspire", "Randall:1999ee"), None);
+    }
+}
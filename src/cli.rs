@@ -0,0 +1,370 @@
+//! Command line options shared by the binaries
+//!
+//! `aux2bib`, `blg2bib`, `log2bib` and `tex2bib` only differ in how they find the keys in their
+//! input.  The options for fetching, caching, formatting and reporting are defined by `app`,
+//! applied to an [`Inspirer`](../struct.Inspirer.html) by `configure`, and the entries of the keys
+//! are fetched and written by `fetch`.
+
+use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use clap::{App, Arg, ArgMatches};
+
+use crate::bib::Entry;
+use crate::cache::Cache;
+use crate::format::{Case, Delimiter, FieldOrder, Format};
+use crate::journals::JournalNames;
+use crate::policy::{Order, Policy};
+use crate::report::{self, Report};
+use crate::source::Local;
+use crate::{Inspirer, InspirerError};
+
+/// The command line of a binary, with the INPUT and OUTPUT arguments and the shared options
+///
+/// Binaries add their own options before calling `get_matches`.
+pub fn app<'a, 'b>(name: &str) -> App<'a, 'b> {
+    App::new(name)
+        .version(crate_version!())
+        .author(crate_authors!(",\n"))
+        .about("gets BibTeX keys from Inspire")
+        .arg(
+            Arg::with_name("INPUT")
+                .help("Sets the file from which to extract BibTeX keys")
+                .index(1),
+        )
+        .arg(
+            Arg::with_name("OUTPUT")
+                .help("Sets the file to which results should be appended")
+                .index(2),
+        )
+        .arg(
+            Arg::with_name("jobs")
+                .short("j")
+                .long("jobs")
+                .takes_value(true)
+                .value_name("N")
+                .help("Sets the number of entries fetched at once from each server"),
+        )
+        .arg(
+            Arg::with_name("cache-ttl")
+                .long("cache-ttl")
+                .takes_value(true)
+                .value_name("DAYS")
+                .help("Sets how many days cached entries are used for"),
+        )
+        .arg(
+            Arg::with_name("no-cache")
+                .long("no-cache")
+                .conflicts_with("cache-ttl")
+                .help("Fetches all entries, without reading or writing the cache"),
+        )
+        .arg(
+            Arg::with_name("offline")
+                .long("offline")
+                .help(
+                    "Takes entries only from the cache and --bib databases, without connecting \
+                     to any server",
+                ),
+        )
+        .arg(
+            Arg::with_name("report")
+                .long("report")
+                .takes_value(true)
+                .value_name("FORMAT")
+                .possible_values(&["text", "json"])
+                .default_value("text")
+                .help("Sets the format of the summary of resolved and unresolved keys"),
+        )
+        .arg(
+            Arg::with_name("report-file")
+                .long("report-file")
+                .takes_value(true)
+                .value_name("FILE")
+                .help("Writes the summary to a file instead of stderr"),
+        )
+        .arg(
+            Arg::with_name("replace")
+                .long("replace")
+                .requires("OUTPUT")
+                .help("Replaces entries already in OUTPUT instead of skipping them"),
+        )
+        .arg(
+            Arg::with_name("backup")
+                .long("backup")
+                .requires("OUTPUT")
+                .help("Keeps the previous version of OUTPUT as OUTPUT.bak"),
+        )
+        .arg(
+            Arg::with_name("format")
+                .long("format")
+                .help("Lays out entries uniformly, instead of as they were fetched"),
+        )
+        .arg(
+            Arg::with_name("entry-case")
+                .long("entry-case")
+                .takes_value(true)
+                .value_name("CASE")
+                .possible_values(&["lower", "upper", "keep"])
+                .requires("format")
+                .help("Sets the case of entry types [default: lower]"),
+        )
+        .arg(
+            Arg::with_name("field-order")
+                .long("field-order")
+                .takes_value(true)
+                .value_name("ORDER")
+                .requires("format")
+                .help("Sets the order of fields: keep, alphabetical, or a list of fields to put first, such as author,title [default: alphabetical]"),
+        )
+        .arg(
+            Arg::with_name("indent")
+                .long("indent")
+                .takes_value(true)
+                .value_name("N")
+                .requires("format")
+                .help("Sets the number of spaces before each field [default: 2]"),
+        )
+        .arg(
+            Arg::with_name("no-align")
+                .long("no-align")
+                .requires("format")
+                .help("Does not align the = of the fields"),
+        )
+        .arg(
+            Arg::with_name("delimiters")
+                .long("delimiters")
+                .takes_value(true)
+                .value_name("DELIMITERS")
+                .possible_values(&["braces", "quotes"])
+                .requires("format")
+                .help("Sets the delimiters of field values [default: braces]"),
+        )
+        .arg(
+            Arg::with_name("journal-names")
+                .long("journal-names")
+                .takes_value(true)
+                .value_name("NAMES")
+                .possible_values(&["macros", "abbreviated", "full"])
+                .help("Replaces the journal macros of ADS entries, such as \\apj, by names [default: macros]"),
+        )
+        .arg(
+            Arg::with_name("bib")
+                .long("bib")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("FILE")
+                .help("Copies entries from FILE, such as a shared bibliography, instead of fetching them"),
+        )
+        .arg(
+            Arg::with_name("config")
+                .long("config")
+                .takes_value(true)
+                .value_name("FILE")
+                .help("Reads the resolution policy from FILE [default: ~/.config/inspirer/config.toml]"),
+        )
+        .arg(
+            Arg::with_name("order")
+                .long("order")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("CLASS=SOURCES")
+                .help("Sets the sources asked for keys of a class, in order, such as ads=inspire,ads"),
+        )
+        .arg(
+            Arg::with_name("disable")
+                .long("disable")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("SOURCE")
+                .help("Never asks SOURCE, one of inspire, ads, arxiv or doi"),
+        )
+        .arg(
+            Arg::with_name("fallback")
+                .long("fallback")
+                .help("Asks the other sources when the one recognising a key fails"),
+        )
+        .arg(
+            Arg::with_name("strict")
+                .long("strict")
+                .help("Fails if any key is not resolved, including keys of unknown format"),
+        )
+}
+
+/// An `Inspirer` set up as the options of `app` say
+///
+/// Invalid option values make the process exit with clap's usage message.
+pub fn configure(matches: &ArgMatches, logger: &slog::Logger) -> Result<Inspirer, InspirerError> {
+    let mut lib = Inspirer::init(Some(logger.new(o!())));
+    if matches.is_present("jobs") {
+        lib = lib.with_concurrency(value_t!(matches, "jobs", usize).unwrap_or_else(|e| e.exit()));
+    }
+    if !matches.is_present("no-cache") {
+        let mut cache = Cache::default_dir().map(Cache::new);
+        if matches.is_present("cache-ttl") {
+            let days = value_t!(matches, "cache-ttl", u64).unwrap_or_else(|e| e.exit());
+            let secs = days.checked_mul(24 * 60 * 60).unwrap_or_else(|| {
+                clap::Error::with_description(
+                    &format!("--cache-ttl {} is too many days", days),
+                    clap::ErrorKind::ValueValidation,
+                )
+                .exit()
+            });
+            cache = cache.map(|cache| cache.with_ttl(Duration::from_secs(secs)));
+        }
+        lib = lib.with_cache(cache);
+    }
+    lib = lib
+        .with_offline(matches.is_present("offline"))
+        .with_replace(matches.is_present("replace"))
+        .with_backup(matches.is_present("backup"));
+    if matches.is_present("journal-names") {
+        lib = lib.with_journal_names(
+            value_t!(matches, "journal-names", JournalNames).unwrap_or_else(|e| e.exit()),
+        );
+    }
+
+    if let Some(file_names) = matches.values_of("bib") {
+        let file_names: Vec<&str> = file_names.collect();
+        lib = lib.with_local_source(Local::open(&file_names)?);
+    }
+
+    // Sources named in the policy must exist, "local" even without --bib
+    let known = |name: &str| name == "local" || lib.sources().iter().any(|s| s.name() == name);
+    let config = match matches.value_of("config") {
+        Some(file_name) => Some(PathBuf::from(file_name)),
+        None => Policy::default_path().filter(|path| path.exists()),
+    };
+    let mut policy = match config {
+        Some(path) => {
+            let policy = Policy::load(&path)?;
+            if let Some(name) = policy.names().into_iter().find(|&name| !known(name)) {
+                return Err(InspirerError::Config {
+                    reason: format!("unknown source {}", name),
+                    path,
+                });
+            }
+            policy
+        }
+        None => Policy::default(),
+    };
+    if matches.is_present("order") {
+        for order in values_t!(matches, "order", Order).unwrap_or_else(|e| e.exit()) {
+            if let Some(name) = std::iter::once(&order.class)
+                .chain(&order.sources)
+                .find(|name| !known(name))
+            {
+                unknown_source(name, "order");
+            }
+            policy = policy.with_order(order);
+        }
+    }
+    for source in matches.values_of("disable").into_iter().flatten() {
+        if !known(&source.to_lowercase()) {
+            unknown_source(source, "disable");
+        }
+        policy = policy.with_disabled(source);
+    }
+    if matches.is_present("fallback") {
+        policy = policy.with_fallback(true);
+    }
+    lib = lib.with_policy(policy);
+    if matches.is_present("format") {
+        let mut format = Format::default().with_align(!matches.is_present("no-align"));
+        if matches.is_present("entry-case") {
+            format = format.with_entry_case(
+                value_t!(matches, "entry-case", Case).unwrap_or_else(|e| e.exit()),
+            );
+        }
+        if matches.is_present("field-order") {
+            format = format.with_field_order(
+                value_t!(matches, "field-order", FieldOrder).unwrap_or_else(|e| e.exit()),
+            );
+        }
+        if matches.is_present("indent") {
+            format =
+                format.with_indent(value_t!(matches, "indent", usize).unwrap_or_else(|e| e.exit()));
+        }
+        if matches.is_present("delimiters") {
+            format = format.with_delimiter(
+                value_t!(matches, "delimiters", Delimiter).unwrap_or_else(|e| e.exit()),
+            );
+        }
+        lib = lib.with_format(Some(format));
+    }
+
+    Ok(lib)
+}
+
+/// Fetch the entries of `keys`, write them to OUTPUT and summarise which keys were resolved
+///
+/// Returns the exit status, which depends on how many keys could be resolved.
+pub fn fetch(
+    lib: &Inspirer,
+    matches: &ArgMatches,
+    logger: &slog::Logger,
+    keys: &[String],
+) -> Result<i32, InspirerError> {
+    info!(logger, "Extracted BibTeX keys";
+          "number_of_keys" => keys.len());
+
+    // Retrieve BibTeX entries from inspire.net
+    info!(logger, "Retrieving entries");
+    let keys: Vec<&str> = keys.iter().map(|k| k.as_str()).collect();
+    let results = lib.bibtex_many_with_source(&keys);
+    let mut report = Report::new(lib, &keys, &results);
+    let mut bibtex_entries: Vec<Entry> = Vec::new();
+    for result in results {
+        match result {
+            Ok((bibtex_entry, _)) => bibtex_entries.push(bibtex_entry),
+            Err(e) => warn!(logger, "Unable to fetch entry";
+                            "error" => e.to_string()),
+        }
+    }
+
+    // Write BibTeX entries to file or stdout
+    report.skipped = lib.put_output(matches.value_of("OUTPUT"), bibtex_entries)?;
+
+    // Summarise which keys were resolved
+    let summary = match matches.value_of("report") {
+        Some("json") => report.to_json() + "\n",
+        _ => report.to_string(),
+    };
+    match matches.value_of("report-file") {
+        Some(file_name) => fs::write(file_name, summary).map_err(InspirerError::Io)?,
+        None => eprint!("{}", summary),
+    }
+
+    info!(logger, "Done");
+
+    Ok(report.exit_code(matches.is_present("strict")))
+}
+
+/// Exit with clap's usage message for a source `name` given to `--option` which does not exist
+fn unknown_source(name: &str, option: &str) -> ! {
+    clap::Error::with_description(
+        &format!("unknown source '{}' given to --{}", name, option),
+        clap::ErrorKind::InvalidValue,
+    )
+    .exit()
+}
+
+/// Exit with the status a binary's run returned, or log its error and exit with
+/// `report::EXIT_IO_ERROR`
+pub fn exit(logger: &slog::Logger, result: Result<i32, InspirerError>) -> ! {
+    match result {
+        Ok(exit_code) => ::std::process::exit(exit_code),
+        Err(ref e) => {
+            error!(logger, e.to_string();
+            "error" => match e.source() {
+                Some(e) => e.to_string(),
+                None => String::new(),
+            });
+            ::std::process::exit(report::EXIT_IO_ERROR);
+        }
+    }
+}
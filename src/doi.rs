@@ -0,0 +1,238 @@
+//! Fetching BibTeX entries for DOIs
+//!
+//! DOIs are looked up on INSPIRE first, since INSPIRE entries are of much better quality.  Papers
+//! INSPIRE does not know about, or all papers if INSPIRE is not to be asked (see
+//! `Api::without_inspire`), are resolved through DOI content negotiation, which asks the
+//! registration agency of the DOI (Crossref, DataCite, ...) for BibTeX.
+//!
+//! In both cases the BibTeX key of the entry is the DOI that was cited.
+
+use regex::Regex;
+use reqwest::Url;
+use slog::DrainExt;
+
+use crate::arxiv::replace_key;
+use crate::http;
+use crate::inspire;
+use crate::InspirerError;
+
+#[derive(Debug, PartialEq)]
+pub struct Doi<'a> {
+    pub doi: &'a str,
+}
+
+/// Create `Doi` from &str
+///
+/// Returns a `Result<Self, ()>` as this can fail.
+///
+/// # Examples
+///
+/// ```
+/// inspirer::doi::Doi::new("10.1103/PhysRevLett.116.061102").unwrap();
+/// ```
+impl<'a> Doi<'a> {
+    #[allow(clippy::result_unit_err)]
+    pub fn new(s: &'a str) -> Result<Self, ()> {
+        if validate_doi(s) {
+            Ok(Doi { doi: s })
+        } else {
+            Err(())
+        }
+    }
+}
+
+/// Test whether a string is a DOI
+///
+/// DOIs are a `10.` prefix with a registrant code, a `/` and a suffix which may contain almost
+/// anything but whitespace.
+///
+/// # Examples
+///
+/// ```
+/// assert!(inspirer::doi::validate_doi("10.1103/PhysRevLett.116.061102"));
+/// assert!(inspirer::doi::validate_doi("10.1088/1475-7516/2016/07/036"));
+/// assert!(!inspirer::doi::validate_doi("Randall:1999ee"));
+/// assert!(!inspirer::doi::validate_doi("hep-th/9901001"));
+/// ```
+pub fn validate_doi(doi: &str) -> bool {
+    lazy_static! {
+        static ref REGEX: Regex = Regex::new(r"^10\.[[:digit:]]{4,9}(?:\.[[:digit:]]+)*/\S+$")
+            .expect("DOI regex compiled during development");
+    }
+
+    REGEX.is_match(doi)
+}
+
+pub struct Api {
+    logger: slog::Logger,
+    inspire: Option<inspire::Client>,
+}
+
+impl Api {
+    /// Initialize API, looking records up on INSPIRE with `inspire`
+    ///
+    /// Either provide a custom slog::Logger or default to the standard `log`
+    /// crate.  The client may be shared with other sources.
+    ///
+    /// # Examples
+    /// ```
+    /// use inspirer::inspire;
+    ///
+    /// inspirer::doi::Api::init(None, inspire::Client::init(None));
+    /// ```
+    pub fn init(logger: Option<slog::Logger>, inspire: inspire::Client) -> Self {
+        let logger = logger.unwrap_or_else(|| slog::Logger::root(slog_stdlog::StdLog.fuse(), o!()));
+
+        Api {
+            logger,
+            inspire: Some(inspire),
+        }
+    }
+
+    /// Never ask INSPIRE, resolving every DOI at doi.org
+    ///
+    /// # Examples
+    /// ```
+    /// use inspirer::inspire;
+    ///
+    /// inspirer::doi::Api::init(None, inspire::Client::init(None)).without_inspire();
+    /// ```
+    pub fn without_inspire(mut self) -> Self {
+        self.inspire = None;
+        self
+    }
+
+    /// Fetch a BibTeX entry for a DOI
+    ///
+    /// INSPIRE is asked first, unless `without_inspire` was set; content negotiation at doi.org
+    /// is used as a fallback.
+    pub fn fetch_bibtex_with_key(&self, key: Doi) -> Result<String, InspirerError> {
+        let inspire = match self.inspire {
+            Some(ref inspire) => inspire,
+            None => return self.fetch_from_doi_org(&key),
+        };
+
+        self.fetch_from_inspire(inspire, &key).or_else(|e| {
+            debug!(self.logger, "Falling back to doi.org";
+                   "key" => key.doi, "error" => e.to_string());
+            self.fetch_from_doi_org(&key)
+        })
+    }
+
+    /// Look the paper up on INSPIRE by its DOI
+    fn fetch_from_inspire(
+        &self,
+        inspire: &inspire::Client,
+        key: &Doi,
+    ) -> Result<String, InspirerError> {
+        let id = inspire::Identifier::Doi(key.doi);
+        let bibtex = inspire.bibtex(id)?;
+
+        with_key(
+            &bibtex,
+            key.doi,
+            &inspire.fetch_url(id, inspire::Serialisation::Bibtex),
+        )
+    }
+
+    /// Ask the registration agency of the DOI for BibTeX
+    fn fetch_from_doi_org(&self, key: &Doi) -> Result<String, InspirerError> {
+        let api_url = doi_url("https://doi.org/", key.doi);
+
+        let bibtex = http::get_accepting(
+            &self.logger,
+            api_url.clone(),
+            Some("application/x-bibtex"),
+            key.doi,
+            "doi.org",
+        )?;
+
+        with_key(&bibtex, key.doi, &api_url)
+    }
+}
+
+/// Append a DOI to a URL, escaping its suffix but keeping its slashes
+fn doi_url(base: &str, doi: &str) -> Url {
+    let mut url = Url::parse(base).expect("Static and parseable");
+    url.path_segments_mut()
+        .expect("Static URL is a base")
+        .pop_if_empty()
+        .extend(doi.split('/'));
+
+    url
+}
+
+/// Give an entry the cited DOI as its key
+///
+/// Content negotiation returns the entry on a single line without a final newline, so one is
+/// added to keep entries apart in the output.
+fn with_key(bibtex: &str, doi: &str, url: &Url) -> Result<String, InspirerError> {
+    let mut entry = replace_key(bibtex, doi).ok_or_else(|| InspirerError::MalformedResponse {
+        url: url.to_string(),
+        reason: "response is not a BibTeX entry".to_string(),
+    })?;
+    if !entry.ends_with('\n') {
+        entry.push('\n');
+    }
+
+    Ok(entry)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_doi() {
+        assert!(validate_doi("10.1103/PhysRevLett.116.061102"));
+        assert!(validate_doi("10.1023/A:1026654312961"));
+        assert!(validate_doi("10.1000.10/123456"));
+        assert!(validate_doi(
+            "10.1002/(SICI)1097-4571(199806)49:8<693::AID-ASI4>3.0.CO;2-0"
+        ));
+        assert!(!validate_doi("10.1103/"));
+        assert!(!validate_doi("10.11/short.registrant"));
+        assert!(!validate_doi("11.1103/PhysRevLett.116.061102"));
+        assert!(!validate_doi("10.1103/PhysRevLett 116"));
+    }
+
+    #[test]
+    fn test_validate_other_keys() {
+        assert!(!validate_doi("Randall:1999ee"));
+        assert!(!validate_doi("1999PhRvL..83.3370R"));
+        assert!(!validate_doi("hep-th/9901001"));
+        assert!(!validate_doi(""));
+    }
+
+    #[test]
+    fn test_doi_url() {
+        assert_eq!(
+            doi_url("https://doi.org/", "10.1103/PhysRevLett.116.061102").as_str(),
+            "https://doi.org/10.1103/PhysRevLett.116.061102"
+        );
+        assert_eq!(
+            doi_url(
+                "https://inspirehep.net/api/doi/",
+                "10.1088/1475-7516/2016/07/036"
+            )
+            .as_str(),
+            "https://inspirehep.net/api/doi/10.1088/1475-7516/2016/07/036"
+        );
+        assert_eq!(
+            doi_url("https://doi.org/", "10.1002/(SICI)49:8<693>3.0.CO;2-0#x").as_str(),
+            "https://doi.org/10.1002/(SICI)49:8%3C693%3E3.0.CO;2-0%23x"
+        );
+    }
+
+    #[test]
+    fn test_with_key() {
+        let url = Url::parse("https://doi.org/10.1103/PhysRevLett.116.061102").unwrap();
+        let negotiated = " @article{Abbott_2016, title={Observation of Gravitational Waves from a Binary Black Hole Merger}, volume={116}, year={2016}}";
+
+        assert_eq!(
+            with_key(negotiated, "10.1103/PhysRevLett.116.061102", &url).unwrap(),
+            "@article{10.1103/PhysRevLett.116.061102, title={Observation of Gravitational Waves from a Binary Black Hole Merger}, volume={116}, year={2016}}\n"
+        );
+        assert!(with_key("<html></html>", "10.1103/PhysRevLett.116.061102", &url).is_err());
+    }
+}
@@ -0,0 +1,389 @@
+//! Uniform layout of BibTeX entries
+//!
+//! INSPIRE and ADS lay out their entries differently.  A [`Format`](struct.Format.html) rewrites
+//! entries from any source in the same style, so that a database does not mix styles.
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::bib::{Entry, Value};
+
+/// Case of entry types and field names
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Case {
+    Lower,
+    Upper,
+    /// As written by the source
+    Keep,
+}
+
+impl Case {
+    fn apply(self, s: &str) -> String {
+        match self {
+            Case::Lower => s.to_lowercase(),
+            Case::Upper => s.to_uppercase(),
+            Case::Keep => s.to_string(),
+        }
+    }
+}
+
+impl FromStr for Case {
+    type Err = FormatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "lower" => Ok(Case::Lower),
+            "upper" => Ok(Case::Upper),
+            "keep" => Ok(Case::Keep),
+            _ => Err(FormatError(format!(
+                "unknown case {}, expected lower, upper or keep",
+                s
+            ))),
+        }
+    }
+}
+
+/// Order of the fields of an entry
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FieldOrder {
+    /// As written by the source
+    Keep,
+    /// Sorted by name
+    Alphabetical,
+    /// The named fields first, in the given order, then the others sorted by name
+    Custom(Vec<String>),
+}
+
+/// Parses `keep`, `alphabetical`, or a comma-separated list of field names
+impl FromStr for FieldOrder {
+    type Err = FormatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "keep" => Ok(FieldOrder::Keep),
+            "alphabetical" => Ok(FieldOrder::Alphabetical),
+            _ => {
+                let names: Vec<String> = s
+                    .split(',')
+                    .map(|name| name.trim().to_lowercase())
+                    .filter(|name| !name.is_empty())
+                    .collect();
+                if names.is_empty() {
+                    Err(FormatError(format!("no field names in {:?}", s)))
+                } else {
+                    Ok(FieldOrder::Custom(names))
+                }
+            }
+        }
+    }
+}
+
+/// Delimiters around text values
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Delimiter {
+    /// `{...}`
+    Braces,
+    /// `"..."`, except for text containing a `"` outside braces, which cannot be quoted
+    Quotes,
+}
+
+impl FromStr for Delimiter {
+    type Err = FormatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "braces" => Ok(Delimiter::Braces),
+            "quotes" => Ok(Delimiter::Quotes),
+            _ => Err(FormatError(format!(
+                "unknown delimiter {}, expected braces or quotes",
+                s
+            ))),
+        }
+    }
+}
+
+/// An option of a `Format` could not be parsed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormatError(String);
+
+impl fmt::Display for FormatError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for FormatError {}
+
+/// A layout for BibTeX entries
+///
+/// By default entry types and field names are lowercase, fields are sorted by name, indented by
+/// two spaces with their `=` aligned, and text is delimited by braces.  Bare numbers are
+/// delimited too, so that it does not matter whether the source wrote `year = 2014` or
+/// `year = "2014"`.  Abbreviations such as `month = apr` are kept as they are.
+///
+/// # Examples
+///
+/// ```
+/// use inspirer::bib::Entry;
+/// use inspirer::format::{Case, Format};
+///
+/// let entry = Entry::parse(
+///     "@ARTICLE{1982PhRvL..48.1220A,\n   author = {{Albrecht}, A.},\n     year = 1982,\n    month = apr,\n}",
+/// )
+/// .unwrap();
+///
+/// assert_eq!(
+///     Format::default().format(&entry),
+///     "@article{1982PhRvL..48.1220A,\n  author = {{Albrecht}, A.},\n  month  = apr,\n  year   = {1982}\n}\n"
+/// );
+/// assert_eq!(
+///     Format::default()
+///         .with_entry_case(Case::Upper)
+///         .with_align(false)
+///         .format(&entry),
+///     "@ARTICLE{1982PhRvL..48.1220A,\n  author = {{Albrecht}, A.},\n  month = apr,\n  year = {1982}\n}\n"
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Format {
+    entry_case: Case,
+    field_case: Case,
+    order: FieldOrder,
+    indent: usize,
+    align: bool,
+    delimiter: Delimiter,
+}
+
+impl Default for Format {
+    fn default() -> Self {
+        Format {
+            entry_case: Case::Lower,
+            field_case: Case::Lower,
+            order: FieldOrder::Alphabetical,
+            indent: 2,
+            align: true,
+            delimiter: Delimiter::Braces,
+        }
+    }
+}
+
+impl Format {
+    /// Set the case of entry types, such as `article`
+    pub fn with_entry_case(mut self, case: Case) -> Self {
+        self.entry_case = case;
+        self
+    }
+
+    /// Set the case of field names
+    pub fn with_field_case(mut self, case: Case) -> Self {
+        self.field_case = case;
+        self
+    }
+
+    pub fn with_field_order(mut self, order: FieldOrder) -> Self {
+        self.order = order;
+        self
+    }
+
+    /// Set the number of spaces before each field
+    pub fn with_indent(mut self, indent: usize) -> Self {
+        self.indent = indent;
+        self
+    }
+
+    /// Set whether the `=` of the fields of an entry are aligned
+    pub fn with_align(mut self, align: bool) -> Self {
+        self.align = align;
+        self
+    }
+
+    pub fn with_delimiter(mut self, delimiter: Delimiter) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    /// Write an entry in this format
+    pub fn format(&self, entry: &Entry) -> String {
+        let mut fields: Vec<(String, &Value)> = entry
+            .fields()
+            .iter()
+            .map(|f| (self.field_case.apply(&f.name), &f.value))
+            .collect();
+
+        match self.order {
+            FieldOrder::Keep => {}
+            FieldOrder::Alphabetical => {
+                fields.sort_by_key(|(name, _)| name.to_lowercase());
+            }
+            FieldOrder::Custom(ref names) => {
+                fields.sort_by_key(|(name, _)| {
+                    let name = name.to_lowercase();
+                    match names.iter().position(|n| *n == name) {
+                        Some(i) => (i, String::new()),
+                        None => (names.len(), name),
+                    }
+                });
+            }
+        }
+
+        let width = if self.align {
+            fields
+                .iter()
+                .map(|(name, _)| name.chars().count())
+                .max()
+                .unwrap_or(0)
+        } else {
+            0
+        };
+
+        let mut text = format!("@{}{{{}", self.entry_case.apply(entry.kind()), entry.key());
+        for (name, value) in fields {
+            text.push_str(&format!(
+                ",\n{:indent$}{:width$} = {}",
+                "",
+                name,
+                self.value(value),
+                indent = self.indent,
+                width = width
+            ));
+        }
+        text.push_str("\n}\n");
+
+        text
+    }
+
+    fn value(&self, value: &Value) -> String {
+        match *value {
+            Value::Text(ref text) | Value::Number(ref text) => {
+                if self.delimiter == Delimiter::Quotes && can_quote(text) {
+                    format!("\"{}\"", text)
+                } else {
+                    format!("{{{}}}", text)
+                }
+            }
+            Value::Macro(ref name) => name.clone(),
+            Value::Concat(ref values) => values
+                .iter()
+                .map(|v| self.value(v))
+                .collect::<Vec<String>>()
+                .join(" # "),
+        }
+    }
+}
+
+/// Whether text can be delimited by quotes, i.e. has no `"` outside braces
+fn can_quote(text: &str) -> bool {
+    let mut depth = 0;
+    for c in text.chars() {
+        match c {
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            '"' if depth == 0 => return false,
+            _ => {}
+        }
+    }
+
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INSPIRE: &str = r#"@article{Higgs:2014aqa,
+      author         = "Higgs, Peter W.",
+      title          = "{Nobel Lecture: Evading the Goldstone theorem}",
+      journal        = "Rev. Mod. Phys.",
+      year           = "2014",
+      SLACcitation   = "%%CITATION = RMPHA,86,851;%%"
+}
+"#;
+
+    const ADS: &str = r#"@ARTICLE{1982PhRvL..48.1220A,
+   author = {{Albrecht}, A. and {Steinhardt}, P.~J.},
+    title = "{Cosmology for grand unified theories}",
+  journal = {Physical Review Letters},
+     year = 1982,
+}
+"#;
+
+    #[test]
+    fn test_sources_look_alike() {
+        let format = Format::default()
+            .with_field_order("author,title,journal,year".parse().unwrap())
+            .with_indent(4);
+
+        assert_eq!(
+            format.format(&Entry::parse(INSPIRE).unwrap()),
+            r#"@article{Higgs:2014aqa,
+    author       = {Higgs, Peter W.},
+    title        = {{Nobel Lecture: Evading the Goldstone theorem}},
+    journal      = {Rev. Mod. Phys.},
+    year         = {2014},
+    slaccitation = {%%CITATION = RMPHA,86,851;%%}
+}
+"#
+        );
+        assert_eq!(
+            format.format(&Entry::parse(ADS).unwrap()),
+            r#"@article{1982PhRvL..48.1220A,
+    author  = {{Albrecht}, A. and {Steinhardt}, P.~J.},
+    title   = {{Cosmology for grand unified theories}},
+    journal = {Physical Review Letters},
+    year    = {1982}
+}
+"#
+        );
+    }
+
+    #[test]
+    fn test_keep() {
+        let format = Format::default()
+            .with_entry_case(Case::Keep)
+            .with_field_case(Case::Keep)
+            .with_field_order(FieldOrder::Keep)
+            .with_align(false)
+            .with_delimiter(Delimiter::Quotes);
+
+        assert_eq!(
+            format.format(&Entry::parse(INSPIRE).unwrap()),
+            r#"@article{Higgs:2014aqa,
+  author = "Higgs, Peter W.",
+  title = "{Nobel Lecture: Evading the Goldstone theorem}",
+  journal = "Rev. Mod. Phys.",
+  year = "2014",
+  SLACcitation = "%%CITATION = RMPHA,86,851;%%"
+}
+"#
+        );
+    }
+
+    #[test]
+    fn test_quotes() {
+        let entry =
+            Entry::parse(r#"@misc{Key, note = {Say "hi"}, title = {{"Quoted"}} # jan}"#).unwrap();
+
+        assert_eq!(
+            Format::default()
+                .with_delimiter(Delimiter::Quotes)
+                .format(&entry),
+            "@misc{Key,\n  note  = {Say \"hi\"},\n  title = \"{\"Quoted\"}\" # jan\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_from_str() {
+        assert_eq!("upper".parse(), Ok(Case::Upper));
+        assert!("title".parse::<Case>().is_err());
+        assert_eq!("quotes".parse(), Ok(Delimiter::Quotes));
+        assert_eq!("keep".parse(), Ok(FieldOrder::Keep));
+        assert_eq!(
+            "Author, title,".parse(),
+            Ok(FieldOrder::Custom(vec![
+                "author".to_string(),
+                "title".to_string()
+            ]))
+        );
+        assert!(",".parse::<FieldOrder>().is_err());
+    }
+}
@@ -0,0 +1,169 @@
+//! HTTP requests, with failures turned into `InspirerError`s
+
+use reqwest::header::Headers;
+use reqwest::{StatusCode, Url};
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::Read;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+
+use crate::InspirerError;
+
+/// Limits on the number of requests sent to each host at once
+///
+/// The limits hold for the requests sent by `get` on threads running `HostLimits::apply`, whichever
+/// source sends them.
+pub(crate) struct HostLimits {
+    limit: usize,
+    /// Number of requests being sent to each host
+    active: Mutex<HashMap<String, usize>>,
+    finished: Condvar,
+}
+
+thread_local! {
+    static LIMITS: RefCell<Option<Arc<HostLimits>>> = const { RefCell::new(None) };
+}
+
+impl HostLimits {
+    /// Allow `limit` requests to each host at once
+    pub(crate) fn new(limit: usize) -> Self {
+        HostLimits {
+            limit: limit.max(1),
+            active: Mutex::new(HashMap::new()),
+            finished: Condvar::new(),
+        }
+    }
+
+    /// Run `f`, keeping the requests it sends on this thread within the limits
+    pub(crate) fn apply<T, F: FnOnce() -> T>(self: &Arc<Self>, f: F) -> T {
+        let previous = LIMITS.with(|limits| limits.replace(Some(Arc::clone(self))));
+        let result = f();
+        LIMITS.with(|limits| limits.replace(previous));
+
+        result
+    }
+
+    /// Wait until a request may be sent to `host`
+    fn acquire(self: &Arc<Self>, host: &str) -> Permit {
+        let mut active = self
+            .active
+            .lock()
+            .expect("no thread panics holding the lock");
+        while active.get(host).is_some_and(|&n| n >= self.limit) {
+            active = self
+                .finished
+                .wait(active)
+                .expect("no thread panics holding the lock");
+        }
+        *active.entry(host.to_string()).or_insert(0) += 1;
+
+        Permit {
+            limits: Arc::clone(self),
+            host: host.to_string(),
+        }
+    }
+}
+
+/// Allows one request to a host, until dropped
+struct Permit {
+    limits: Arc<HostLimits>,
+    host: String,
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        if let Ok(mut active) = self.limits.active.lock() {
+            if let Some(n) = active.get_mut(&self.host) {
+                *n -= 1;
+            }
+        }
+        self.limits.finished.notify_all();
+    }
+}
+
+/// GET a URL and return the body of a successful response
+///
+/// `404 Not Found` is reported as `InspirerError::NotFound` for `key` at `source`.  Within
+/// `HostLimits::apply`, waits until the host may be sent another request.
+pub(crate) fn get(
+    logger: &slog::Logger,
+    url: Url,
+    key: &str,
+    source: &'static str,
+) -> Result<String, InspirerError> {
+    get_accepting(logger, url, None, key, source)
+}
+
+/// Like `get`, but asking for a particular media type with an `Accept` header
+pub(crate) fn get_accepting(
+    logger: &slog::Logger,
+    url: Url,
+    accept: Option<&str>,
+    key: &str,
+    source: &'static str,
+) -> Result<String, InspirerError> {
+    let _permit = LIMITS
+        .with(|limits| limits.borrow().clone())
+        .map(|limits| limits.acquire(url.host_str().unwrap_or("")));
+
+    debug!(logger, "Querying API";
+           "URL" => url.to_string());
+    let network_error = |e: reqwest::Error| InspirerError::Network {
+        url: url.to_string(),
+        status: None,
+        reason: e.to_string(),
+    };
+    let mut headers = Headers::new();
+    if let Some(accept) = accept {
+        headers.set_raw("Accept", accept.to_string());
+    }
+    let mut response = reqwest::Client::new()
+        .and_then(|client| client.get(url.clone())?.headers(headers).send())
+        .map_err(network_error)?;
+    debug!(logger, "GET request completed";
+           "HTTP response status" => response.status().to_string());
+
+    match response.status() {
+        status if status.is_success() => {}
+        StatusCode::NotFound => {
+            return Err(InspirerError::NotFound {
+                key: key.to_string(),
+                source,
+            })
+        }
+        StatusCode::TooManyRequests => {
+            let retry_after = response
+                .headers()
+                .get_raw("Retry-After")
+                .and_then(|raw| raw.one())
+                .and_then(|value| std::str::from_utf8(value).ok())
+                .and_then(|value| value.trim().parse().ok())
+                .map(Duration::from_secs);
+
+            return Err(InspirerError::RateLimited {
+                url: url.to_string(),
+                retry_after,
+            });
+        }
+        status => {
+            return Err(InspirerError::Network {
+                url: url.to_string(),
+                status: Some(status.as_u16()),
+                reason: status.canonical_reason().unwrap_or("").to_string(),
+            })
+        }
+    }
+
+    let mut data = String::new();
+    response
+        .read_to_string(&mut data)
+        .map_err(|e| InspirerError::Network {
+            url: url.to_string(),
+            status: None,
+            reason: e.to_string(),
+        })?;
+
+    Ok(data)
+}
@@ -0,0 +1,482 @@
+//! A client for the INSPIRE REST API
+//!
+//! Records are looked up by texkey, arXiv eprint, DOI or record id, and returned in one of the
+//! serialisations INSPIRE offers, see <https://github.com/inspirehep/rest-api-doc>.  The base URL
+//! of the API can be changed, e.g. to a mirror or to a stub server in tests.
+
+use regex::Regex;
+use reqwest::Url;
+use slog::DrainExt;
+
+use crate::http;
+use crate::InspirerError;
+
+/// The INSPIRE API, unless set with `Client::with_base_url`
+pub const DEFAULT_BASE_URL: &str = "https://inspirehep.net/api/";
+
+/// Ways to identify an INSPIRE record
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Identifier<'a> {
+    /// A BibTeX key assigned by INSPIRE, such as `Randall:1999ee`
+    Texkey(&'a str),
+    /// An arXiv identifier, such as `hep-th/9906064`
+    Eprint(&'a str),
+    /// A DOI, such as `10.1103/PhysRevLett.83.3370`
+    Doi(&'a str),
+    /// The number of the record, such as `502520`
+    Recid(u64),
+}
+
+impl<'a> Identifier<'a> {
+    /// The identifier as it was cited, used in errors
+    fn key(&self) -> String {
+        match *self {
+            Identifier::Texkey(s) | Identifier::Eprint(s) | Identifier::Doi(s) => s.to_string(),
+            Identifier::Recid(recid) => recid.to_string(),
+        }
+    }
+}
+
+/// Formats INSPIRE returns records in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Serialisation {
+    Bibtex,
+    /// A `\bibitem` in the style of European journals
+    LatexEu,
+}
+
+impl Serialisation {
+    fn name(self) -> &'static str {
+        match self {
+            Serialisation::Bibtex => "bibtex",
+            Serialisation::LatexEu => "latex-eu",
+        }
+    }
+}
+
+/// Test whether a string looks like an INSPIRE texkey
+///
+/// Texkeys are the name of the first author or collaboration, the year and a few random letters.
+///
+/// # Examples
+///
+/// ```
+/// assert!(inspirer::inspire::validate_texkey("Randall:1999ee"));
+/// assert!(inspirer::inspire::validate_texkey("Arkani-Hamed:1998jc"));
+/// assert!(!inspirer::inspire::validate_texkey("1999PhRvL..83.3370R"));
+/// ```
+pub fn validate_texkey(key: &str) -> bool {
+    lazy_static! {
+        static ref REGEX: Regex =
+            Regex::new(r"^[[:alpha:]][[:alpha:].'-]*:[[:digit:]]{4}[[:alpha:]]{2,3}$")
+                .expect("texkey regex compiled during development");
+    }
+
+    REGEX.is_match(key)
+}
+
+/// A client of the INSPIRE API
+///
+/// Requests are sent to `DEFAULT_BASE_URL` unless another base URL is set.  Clients are cheap to
+/// clone, so that the sources looking records up on INSPIRE can share one.
+#[derive(Clone)]
+pub struct Client {
+    logger: slog::Logger,
+    base_url: Url,
+}
+
+impl Client {
+    /// Initialize a client of the INSPIRE API at `DEFAULT_BASE_URL`
+    ///
+    /// Either provide a custom slog::Logger or default to the standard `log`
+    /// crate.
+    ///
+    /// # Examples
+    /// ```
+    /// inspirer::inspire::Client::init(None);
+    /// ```
+    pub fn init(logger: Option<slog::Logger>) -> Self {
+        Client {
+            logger: logger.unwrap_or_else(|| slog::Logger::root(slog_stdlog::StdLog.fuse(), o!())),
+            base_url: Url::parse(DEFAULT_BASE_URL).expect("Static and parseable"),
+        }
+    }
+
+    /// Send requests to another instance of the API
+    ///
+    /// # Examples
+    /// ```
+    /// use reqwest::Url;
+    ///
+    /// let client = inspirer::inspire::Client::init(None)
+    ///     .with_base_url(Url::parse("http://localhost:5000/api/").unwrap());
+    ///
+    /// assert_eq!(client.base_url().as_str(), "http://localhost:5000/api/");
+    /// ```
+    pub fn with_base_url(mut self, base_url: Url) -> Self {
+        self.base_url = base_url;
+        self
+    }
+
+    /// The URL requests are sent to, `DEFAULT_BASE_URL` unless set with `with_base_url`
+    pub fn base_url(&self) -> &Url {
+        &self.base_url
+    }
+
+    /// Fetch a record in a serialisation
+    ///
+    /// The BibTeX key of the record is INSPIRE's texkey, whichever identifier was used.
+    pub fn fetch(
+        &self,
+        id: Identifier,
+        serialisation: Serialisation,
+    ) -> Result<String, InspirerError> {
+        match id {
+            Identifier::Texkey(texkey) => {
+                self.search(&texkey_query(texkey), serialisation)?
+                    .ok_or(InspirerError::NotFound {
+                        key: texkey.to_string(),
+                        source: "INSPIRE",
+                    })
+            }
+            _ => http::get(
+                &self.logger,
+                self.fetch_url(id, serialisation),
+                &id.key(),
+                "INSPIRE",
+            ),
+        }
+    }
+
+    /// The URL `fetch` requests a record from, e.g. to report a response which is not as expected
+    ///
+    /// # Examples
+    /// ```
+    /// use inspirer::inspire::{Client, Identifier, Serialisation};
+    ///
+    /// let url = Client::init(None).fetch_url(Identifier::Eprint("hep-th/9906064"), Serialisation::Bibtex);
+    ///
+    /// assert_eq!(url.as_str(), "https://inspirehep.net/api/arxiv/hep-th/9906064?format=bibtex");
+    /// ```
+    pub fn fetch_url(&self, id: Identifier, serialisation: Serialisation) -> Url {
+        match id {
+            Identifier::Texkey(texkey) => self.search_url(&texkey_query(texkey), serialisation),
+            _ => {
+                let mut url = self.record_url(id);
+                url.query_pairs_mut()
+                    .append_pair("format", serialisation.name());
+
+                url
+            }
+        }
+    }
+
+    /// Fetch the BibTeX entry of a record
+    pub fn bibtex(&self, id: Identifier) -> Result<String, InspirerError> {
+        self.fetch(id, Serialisation::Bibtex)
+    }
+
+    /// Look up the number of a record in its JSON metadata
+    pub fn recid(&self, id: Identifier) -> Result<u64, InspirerError> {
+        let (mut url, is_search) = match id {
+            Identifier::Recid(recid) => return Ok(recid),
+            Identifier::Texkey(texkey) => (self.query_url(&texkey_query(texkey)), true),
+            _ => (self.record_url(id), false),
+        };
+        url.query_pairs_mut()
+            .append_pair("fields", "control_number");
+
+        let text = http::get_accepting(
+            &self.logger,
+            url.clone(),
+            Some("application/json"),
+            &id.key(),
+            "INSPIRE",
+        )?;
+        let json: serde_json::Value =
+            serde_json::from_str(&text).map_err(|e| InspirerError::MalformedResponse {
+                url: url.to_string(),
+                reason: e.to_string(),
+            })?;
+
+        let record = if is_search {
+            match json["hits"]["hits"].get(0) {
+                Some(hit) => hit,
+                None if json["hits"]["hits"].is_array() => {
+                    return Err(InspirerError::NotFound {
+                        key: id.key(),
+                        source: "INSPIRE",
+                    })
+                }
+                None => &serde_json::Value::Null,
+            }
+        } else {
+            &json
+        };
+
+        record["metadata"]["control_number"]
+            .as_u64()
+            .ok_or_else(|| InspirerError::MalformedResponse {
+                url: url.to_string(),
+                reason: "no control_number in record".to_string(),
+            })
+    }
+
+    /// Search the literature, returning the first record found
+    ///
+    /// `query` is in INSPIRE's search syntax, e.g. `texkeys:"Randall:1999ee"`.
+    pub fn search(
+        &self,
+        query: &str,
+        serialisation: Serialisation,
+    ) -> Result<Option<String>, InspirerError> {
+        let url = self.search_url(query, serialisation);
+        let text = http::get(&self.logger, url, query, "INSPIRE")?;
+
+        // A search without results is answered with an empty body
+        if text.trim().is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(text))
+        }
+    }
+
+    /// The URL `search` requests
+    pub fn search_url(&self, query: &str, serialisation: Serialisation) -> Url {
+        let mut url = self.query_url(query);
+        url.query_pairs_mut()
+            .append_pair("format", serialisation.name());
+
+        url
+    }
+
+    fn query_url(&self, query: &str) -> Url {
+        let mut url = self.url(&["literature"]);
+        url.query_pairs_mut()
+            .append_pair("q", query)
+            .append_pair("size", "1");
+
+        url
+    }
+
+    fn record_url(&self, id: Identifier) -> Url {
+        match id {
+            Identifier::Texkey(_) => unreachable!("texkeys are searched for"),
+            Identifier::Eprint(eprint) => self.url(&["arxiv", eprint]),
+            Identifier::Doi(doi) => self.url(&["doi", doi]),
+            Identifier::Recid(recid) => self.url(&["literature", &recid.to_string()]),
+        }
+    }
+
+    /// Append path segments to the base URL, escaping each but keeping slashes within them
+    fn url(&self, segments: &[&str]) -> Url {
+        let mut url = self.base_url.clone();
+        url.path_segments_mut()
+            .expect("API URL is a base")
+            .pop_if_empty()
+            .extend(segments.iter().flat_map(|s| s.split('/')));
+
+        url
+    }
+}
+
+/// The search for the record with a texkey
+fn texkey_query(texkey: &str) -> String {
+    format!("texkeys:\"{}\"", texkey)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::sync::mpsc;
+    use std::thread;
+
+    const BIBTEX: &str =
+        "@article{Randall:1999ee,\n    author = \"Randall, Lisa and Sundrum, Raman\"\n}\n";
+
+    /// Answer one request per response with a stub server, sending back the request lines
+    fn serve(responses: Vec<(&'static str, &'static str)>) -> (Url, mpsc::Receiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("can bind to a local port");
+        let base_url = Url::parse(&format!(
+            "http://{}/api/",
+            listener.local_addr().expect("listener has an address")
+        ))
+        .unwrap();
+        let (sender, receiver) = mpsc::channel();
+
+        thread::spawn(move || {
+            for (status, body) in responses {
+                let (mut stream, _) = listener.accept().expect("client connects");
+
+                let mut request = Vec::new();
+                let mut buffer = [0; 1024];
+                while !request.ends_with(b"\r\n\r\n") {
+                    let n = stream.read(&mut buffer).expect("can read request");
+                    if n == 0 {
+                        break;
+                    }
+                    request.extend_from_slice(&buffer[..n]);
+                }
+                let request = String::from_utf8_lossy(&request).into_owned();
+                let _ = sender.send(request);
+
+                write!(
+                    stream,
+                    "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                )
+                .expect("can write response");
+            }
+        });
+
+        (base_url, receiver)
+    }
+
+    fn client(base_url: Url) -> Client {
+        Client::init(Some(slog::Logger::root(slog::Discard, o!()))).with_base_url(base_url)
+    }
+
+    /// The path and query of a request
+    fn request_line(request: &str) -> &str {
+        request
+            .lines()
+            .next()
+            .and_then(|line| line.split(' ').nth(1))
+            .unwrap_or("")
+    }
+
+    #[test]
+    fn test_validate_texkey() {
+        assert!(validate_texkey("Randall:1999ee"));
+        assert!(validate_texkey("ATLAS:2012yve"));
+        assert!(validate_texkey("D'Hoker:2002aw"));
+        assert!(validate_texkey("Arkani-Hamed:1998jc"));
+        assert!(!validate_texkey("Randall:99ee"));
+        assert!(!validate_texkey("hep-th/9906064"));
+        assert!(!validate_texkey("10.1103/PhysRevLett.83.3370"));
+        assert!(!validate_texkey(":1999ee"));
+    }
+
+    #[test]
+    fn test_fetch() {
+        let (base_url, requests) = serve(vec![
+            ("200 OK", BIBTEX),
+            ("200 OK", BIBTEX),
+            ("200 OK", BIBTEX),
+            (
+                "200 OK",
+                "\\bibitem{Randall:1999ee}\nL.~Randall and R.~Sundrum,\n",
+            ),
+        ]);
+        let client = client(base_url);
+
+        assert_eq!(
+            client.bibtex(Identifier::Texkey("Randall:1999ee")).unwrap(),
+            BIBTEX
+        );
+        let request = requests.recv().unwrap();
+        assert_eq!(
+            request_line(&request),
+            "/api/literature?q=texkeys%3A%22Randall%3A1999ee%22&size=1&format=bibtex"
+        );
+        let url = client.fetch_url(Identifier::Texkey("Randall:1999ee"), Serialisation::Bibtex);
+        assert_eq!(
+            format!("{}?{}", url.path(), url.query().unwrap()),
+            request_line(&request)
+        );
+
+        client.bibtex(Identifier::Eprint("hep-th/9906064")).unwrap();
+        assert_eq!(
+            request_line(&requests.recv().unwrap()),
+            "/api/arxiv/hep-th/9906064?format=bibtex"
+        );
+
+        client
+            .bibtex(Identifier::Doi("10.1103/PhysRevLett.83.3370"))
+            .unwrap();
+        assert_eq!(
+            request_line(&requests.recv().unwrap()),
+            "/api/doi/10.1103/PhysRevLett.83.3370?format=bibtex"
+        );
+
+        assert!(client
+            .fetch(Identifier::Recid(502520), Serialisation::LatexEu)
+            .unwrap()
+            .starts_with("\\bibitem{Randall:1999ee}"));
+        assert_eq!(
+            request_line(&requests.recv().unwrap()),
+            "/api/literature/502520?format=latex-eu"
+        );
+    }
+
+    #[test]
+    fn test_fetch_not_found() {
+        let (base_url, _requests) = serve(vec![("200 OK", ""), ("404 Not Found", "")]);
+        let client = client(base_url);
+
+        match client.bibtex(Identifier::Texkey("Nobody:2099zz")) {
+            Err(InspirerError::NotFound { key, source }) => {
+                assert_eq!(key, "Nobody:2099zz");
+                assert_eq!(source, "INSPIRE");
+            }
+            r => panic!("unexpected result {:?}", r),
+        }
+        match client.bibtex(Identifier::Recid(0)) {
+            Err(InspirerError::NotFound { key, .. }) => assert_eq!(key, "0"),
+            r => panic!("unexpected result {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_recid() {
+        let (base_url, requests) = serve(vec![
+            (
+                "200 OK",
+                r#"{"hits": {"hits": [{"id": "502520", "metadata": {"control_number": 502520}}], "total": 1}}"#,
+            ),
+            (
+                "200 OK",
+                r#"{"id": "502520", "metadata": {"control_number": 502520}}"#,
+            ),
+            ("200 OK", r#"{"hits": {"hits": [], "total": 0}}"#),
+            ("200 OK", "<html>Not JSON</html>"),
+        ]);
+        let client = client(base_url);
+
+        assert_eq!(
+            client.recid(Identifier::Texkey("Randall:1999ee")).unwrap(),
+            502520
+        );
+        let request = requests.recv().unwrap();
+        assert_eq!(
+            request_line(&request),
+            "/api/literature?q=texkeys%3A%22Randall%3A1999ee%22&size=1&fields=control_number"
+        );
+        assert!(request.to_lowercase().contains("accept: application/json"));
+
+        assert_eq!(
+            client.recid(Identifier::Eprint("hep-th/9906064")).unwrap(),
+            502520
+        );
+        assert_eq!(
+            request_line(&requests.recv().unwrap()),
+            "/api/arxiv/hep-th/9906064?fields=control_number"
+        );
+
+        assert_eq!(client.recid(Identifier::Recid(42)).unwrap(), 42);
+
+        match client.recid(Identifier::Texkey("Nobody:2099zz")) {
+            Err(InspirerError::NotFound { .. }) => {}
+            r => panic!("unexpected result {:?}", r),
+        }
+        match client.recid(Identifier::Doi("10.1103/PhysRevLett.83.3370")) {
+            Err(InspirerError::MalformedResponse { .. }) => {}
+            r => panic!("unexpected result {:?}", r),
+        }
+    }
+}
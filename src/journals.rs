@@ -0,0 +1,188 @@
+//! Journal macros of ADS entries
+//!
+//! ADS abbreviates journal names with the macros of AASTeX, e.g. `journal = {\apj}`, which are
+//! undefined in documents which do not use AASTeX.  They can be replaced by the names they stand
+//! for.
+
+use regex::{Captures, Regex};
+
+use std::fmt;
+use std::str::FromStr;
+
+/// AASTeX journal macros, without the backslash, with their abbreviated and full names
+#[rustfmt::skip]
+pub const JOURNALS: &[(&str, &str, &str)] = &[
+    ("aj", "AJ", "Astronomical Journal"),
+    ("actaa", "Acta Astron.", "Acta Astronomica"),
+    ("araa", "ARA\\&A", "Annual Review of Astronomy and Astrophysics"),
+    ("apj", "ApJ", "Astrophysical Journal"),
+    ("apjl", "ApJ", "Astrophysical Journal Letters"),
+    ("apjs", "ApJS", "Astrophysical Journal Supplement Series"),
+    ("ao", "Appl.~Opt.", "Applied Optics"),
+    ("apss", "Ap\\&SS", "Astrophysics and Space Science"),
+    ("aap", "A\\&A", "Astronomy and Astrophysics"),
+    ("aapr", "A\\&A~Rev.", "Astronomy and Astrophysics Reviews"),
+    ("aaps", "A\\&AS", "Astronomy and Astrophysics Supplement Series"),
+    ("azh", "AZh", "Astronomicheskii Zhurnal"),
+    ("baas", "BAAS", "Bulletin of the American Astronomical Society"),
+    ("bac", "Bull. astr. Inst. Czechosl.", "Bulletin of the Astronomical Institutes of Czechoslovakia"),
+    ("caa", "Chinese Astron. Astrophys.", "Chinese Astronomy and Astrophysics"),
+    ("cjaa", "Chinese J. Astron. Astrophys.", "Chinese Journal of Astronomy and Astrophysics"),
+    ("icarus", "Icarus", "Icarus"),
+    ("jcap", "J. Cosmology Astropart. Phys.", "Journal of Cosmology and Astroparticle Physics"),
+    ("jrasc", "JRASC", "Journal of the Royal Astronomical Society of Canada"),
+    ("memras", "MmRAS", "Memoirs of the Royal Astronomical Society"),
+    ("mnras", "MNRAS", "Monthly Notices of the Royal Astronomical Society"),
+    ("na", "New A", "New Astronomy"),
+    ("nar", "New A Rev.", "New Astronomy Reviews"),
+    ("pra", "Phys.~Rev.~A", "Physical Review A"),
+    ("prb", "Phys.~Rev.~B", "Physical Review B"),
+    ("prc", "Phys.~Rev.~C", "Physical Review C"),
+    ("prd", "Phys.~Rev.~D", "Physical Review D"),
+    ("pre", "Phys.~Rev.~E", "Physical Review E"),
+    ("prl", "Phys.~Rev.~Lett.", "Physical Review Letters"),
+    ("pasa", "PASA", "Publications of the Astronomical Society of Australia"),
+    ("pasp", "PASP", "Publications of the Astronomical Society of the Pacific"),
+    ("pasj", "PASJ", "Publications of the Astronomical Society of Japan"),
+    ("rmxaa", "Rev. Mexicana Astron. Astrofis.", "Revista Mexicana de Astronomia y Astrofisica"),
+    ("qjras", "QJRAS", "Quarterly Journal of the Royal Astronomical Society"),
+    ("skytel", "S\\&T", "Sky and Telescope"),
+    ("solphys", "Sol.~Phys.", "Solar Physics"),
+    ("sovast", "Soviet~Ast.", "Soviet Astronomy"),
+    ("ssr", "Space~Sci.~Rev.", "Space Science Reviews"),
+    ("zap", "ZAp", "Zeitschrift fuer Astrophysik"),
+    ("nat", "Nature", "Nature"),
+    ("iaucirc", "IAU~Circ.", "IAU Circulars"),
+    ("aplett", "Astrophys.~Lett.", "Astrophysics Letters"),
+    ("apspr", "Astrophys.~Space~Phys.~Res.", "Astrophysics Space Physics Research"),
+    ("bain", "Bull.~Astron.~Inst.~Netherlands", "Bulletin of the Astronomical Institutes of the Netherlands"),
+    ("fcp", "Fund.~Cosmic~Phys.", "Fundamentals of Cosmic Physics"),
+    ("gca", "Geochim.~Cosmochim.~Acta", "Geochimica et Cosmochimica Acta"),
+    ("grl", "Geophys.~Res.~Lett.", "Geophysical Research Letters"),
+    ("jcp", "J.~Chem.~Phys.", "Journal of Chemical Physics"),
+    ("jgr", "J.~Geophys.~Res.", "Journal of Geophysical Research"),
+    ("jqsrt", "J.~Quant.~Spec.~Radiat.~Transf.", "Journal of Quantitative Spectroscopy and Radiative Transfer"),
+    ("memsai", "Mem.~Soc.~Astron.~Italiana", "Memorie della Societa Astronomica Italiana"),
+    ("nphysa", "Nucl.~Phys.~A", "Nuclear Physics A"),
+    ("physrep", "Phys.~Rep.", "Physics Reports"),
+    ("physscr", "Phys.~Scr", "Physica Scripta"),
+    ("planss", "Planet.~Space~Sci.", "Planetary and Space Science"),
+    ("procspie", "Proc.~SPIE", "Proceedings of the SPIE"),
+];
+
+/// How journal macros are written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JournalNames {
+    /// As macros, e.g. `\apj`
+    Macros,
+    /// As the abbreviations AASTeX defines, e.g. `ApJ`
+    Abbreviated,
+    /// As full names, e.g. `Astrophysical Journal`
+    Full,
+}
+
+impl FromStr for JournalNames {
+    type Err = UnknownJournalNames;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "macros" => Ok(JournalNames::Macros),
+            "abbreviated" => Ok(JournalNames::Abbreviated),
+            "full" => Ok(JournalNames::Full),
+            _ => Err(UnknownJournalNames(s.to_string())),
+        }
+    }
+}
+
+/// A `JournalNames` could not be parsed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownJournalNames(String);
+
+impl fmt::Display for UnknownJournalNames {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "unknown journal names {}, expected macros, abbreviated or full",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for UnknownJournalNames {}
+
+/// Replace the journal macros in `text`
+///
+/// Macros which are not in `JOURNALS` are left alone.
+///
+/// # Examples
+///
+/// ```
+/// use inspirer::journals::{self, JournalNames};
+///
+/// let entry = "@ARTICLE{1998MNRAS.301..759F,\n  journal = {\\mnras},\n}";
+///
+/// assert_eq!(
+///     journals::expand(entry, JournalNames::Full),
+///     "@ARTICLE{1998MNRAS.301..759F,\n  journal = {Monthly Notices of the Royal Astronomical Society},\n}"
+/// );
+/// assert_eq!(
+///     journals::expand(entry, JournalNames::Abbreviated),
+///     "@ARTICLE{1998MNRAS.301..759F,\n  journal = {MNRAS},\n}"
+/// );
+/// ```
+pub fn expand(text: &str, names: JournalNames) -> String {
+    lazy_static! {
+        static ref MACRO_REGEX: Regex = Regex::new(r"\\(?P<name>[[:alpha:]]+)")
+            .expect("macro regex compiled during development");
+    }
+
+    if names == JournalNames::Macros {
+        return text.to_string();
+    }
+
+    MACRO_REGEX
+        .replace_all(text, |c: &Captures| {
+            match JOURNALS.iter().find(|&&(name, _, _)| name == &c["name"]) {
+                Some(&(_, abbreviated, _)) if names == JournalNames::Abbreviated => {
+                    abbreviated.to_string()
+                }
+                Some(&(_, _, full)) => full.to_string(),
+                None => c[0].to_string(),
+            }
+        })
+        .into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand() {
+        let text = r"journal = {\apjl}, note = {\prd, \textit{not} \apjx \apj}";
+
+        assert_eq!(
+            expand(text, JournalNames::Abbreviated),
+            r"journal = {ApJ}, note = {Phys.~Rev.~D, \textit{not} \apjx ApJ}"
+        );
+        assert_eq!(
+            expand(text, JournalNames::Full),
+            r"journal = {Astrophysical Journal Letters}, note = {Physical Review D, \textit{not} \apjx Astrophysical Journal}"
+        );
+        assert_eq!(expand(text, JournalNames::Macros), text);
+    }
+
+    #[test]
+    fn test_table() {
+        for &(name, abbreviated, full) in JOURNALS {
+            assert!(name.chars().all(|c| c.is_ascii_lowercase()), "{}", name);
+            assert!(!abbreviated.is_empty() && !full.is_empty());
+            assert_eq!(
+                JOURNALS.iter().filter(|&&(n, _, _)| n == name).count(),
+                1,
+                "{} is unique",
+                name
+            );
+        }
+    }
+}
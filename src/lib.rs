@@ -1,4 +1,5 @@
 use std::fmt;
+use std::time::Duration;
 
 /// Crate errors
 ///
@@ -6,6 +7,45 @@ use std::fmt;
 #[derive(Debug)]
 pub enum InspirerError {
     Io(::std::io::Error),
+    /// The source has no entry for the key
+    NotFound {
+        key: String,
+        source: &'static str,
+    },
+    /// The key does not look like a key of any known source
+    UnknownKeyFormat {
+        key: String,
+    },
+    /// The entry is not cached, and could not be fetched because we are offline
+    Offline {
+        key: String,
+    },
+    /// The request failed, either without an answer or with an HTTP error status
+    Network {
+        url: String,
+        status: Option<u16>,
+        reason: String,
+    },
+    /// The server asked us to make fewer requests (HTTP 429)
+    RateLimited {
+        url: String,
+        retry_after: Option<Duration>,
+    },
+    /// The server answered with something other than the expected BibTeX or metadata
+    MalformedResponse {
+        url: String,
+        reason: String,
+    },
+    /// The fetched entry could not be parsed
+    InvalidEntry {
+        key: String,
+        reason: String,
+    },
+    /// The configuration file could not be parsed
+    Config {
+        path: PathBuf,
+        reason: String,
+    },
     #[doc(hidden)]
     __Nonexhaustive,
 }
@@ -14,6 +54,51 @@ impl std::fmt::Display for InspirerError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             InspirerError::Io(_) => write!(f, "IO Error"),
+            InspirerError::NotFound { key, source } => {
+                write!(f, "{} has no entry for {}", source, key)
+            }
+            InspirerError::UnknownKeyFormat { key } => {
+                write!(f, "{} does not look like a key of any known source", key)
+            }
+            InspirerError::Offline { key } => {
+                write!(f, "{} is not cached and cannot be fetched offline", key)
+            }
+            InspirerError::Network {
+                url,
+                status: Some(status),
+                reason,
+            } => write!(
+                f,
+                "Request to {} failed with HTTP {}: {}",
+                url, status, reason
+            ),
+            InspirerError::Network {
+                url,
+                status: None,
+                reason,
+            } => write!(f, "Request to {} failed: {}", url, reason),
+            InspirerError::RateLimited {
+                url,
+                retry_after: Some(retry_after),
+            } => write!(
+                f,
+                "Rate limited by {}, retry after {} s",
+                url,
+                retry_after.as_secs()
+            ),
+            InspirerError::RateLimited {
+                url,
+                retry_after: None,
+            } => write!(f, "Rate limited by {}", url),
+            InspirerError::MalformedResponse { url, reason } => {
+                write!(f, "Unexpected response from {}: {}", url, reason)
+            }
+            InspirerError::InvalidEntry { key, reason } => {
+                write!(f, "The entry for {} is not valid BibTeX: {}", key, reason)
+            }
+            InspirerError::Config { path, reason } => {
+                write!(f, "Invalid configuration in {}: {}", path.display(), reason)
+            }
             InspirerError::__Nonexhaustive => unreachable!(),
         }
     }
@@ -28,9 +113,6 @@ impl std::error::Error for InspirerError {
     }
 }
 
-use libads;
-use libinspire;
-
 /// Re-export slog
 ///
 /// Users of this library can, but don't have to use slog to build their own loggers
@@ -43,18 +125,63 @@ use slog::DrainExt;
 #[macro_use]
 extern crate lazy_static;
 
+#[macro_use(crate_version, crate_authors, value_t, values_t)]
+extern crate clap;
+
+#[macro_use]
+extern crate serde_json;
+
 use regex::Regex;
 
+pub mod arxiv;
+pub mod auxfile;
+pub mod bcf;
+pub mod bib;
+pub mod blg;
+pub mod cache;
+pub mod cli;
+pub mod doi;
+pub mod format;
+mod http;
+pub mod inspire;
+pub mod journals;
+mod output;
+pub mod policy;
+pub mod report;
+pub mod source;
+pub mod tex;
+mod xml;
+
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
 use std::fs::File;
 use std::io::{BufReader, Read};
 use std::io::{BufWriter, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
 
 pub struct Inspirer {
     logger: slog::Logger,
-    inspire: libinspire::Api,
-    ads: libads::Api,
+    sources: Vec<Box<dyn source::Source>>,
+    inspire: inspire::Client,
+    policy: policy::Policy,
+    concurrency: usize,
+    limits: Arc<http::HostLimits>,
+    cache: Option<cache::Cache>,
+    offline: bool,
+    replace: bool,
+    backup: bool,
+    format: Option<format::Format>,
+    journal_names: journals::JournalNames,
 }
 
+/// Number of requests `Inspirer::bibtex_many` sends to each host at once, unless set with
+/// `Inspirer::with_concurrency`
+pub const DEFAULT_CONCURRENCY: usize = 4;
+
 impl Inspirer {
     /// Initialize 'Inspirer'
     ///
@@ -67,13 +194,265 @@ impl Inspirer {
     /// ```
     pub fn init(logger: Option<slog::Logger>) -> Self {
         let logger = logger.unwrap_or_else(|| slog::Logger::root(slog_stdlog::StdLog.fuse(), o!()));
+        let inspire = inspire::Client::init(Some(logger.clone()));
 
         Inspirer {
+            sources: source::builtin_with_client(inspire.clone()),
+            inspire: inspire,
             logger: logger,
-            // inspire: libinspire::Api::init(Some(logger)),
-            inspire: libinspire::Api::init(None),
-            ads: libads::Api::init(None),
+            policy: policy::Policy::default(),
+            concurrency: DEFAULT_CONCURRENCY,
+            limits: Arc::new(http::HostLimits::new(DEFAULT_CONCURRENCY)),
+            cache: None,
+            offline: false,
+            replace: false,
+            backup: false,
+            format: None,
+            journal_names: journals::JournalNames::Macros,
+        }
+    }
+
+    /// Set how many requests `bibtex_many` may send to each host at once
+    ///
+    /// A limit of 0 is treated as 1.
+    ///
+    /// # Examples
+    /// ```
+    /// inspirer::Inspirer::init(None).with_concurrency(8);
+    /// ```
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self.limits = Arc::new(http::HostLimits::new(self.concurrency));
+        self
+    }
+
+    /// Set the cache of fetched entries, or disable caching with `None`, the default
+    ///
+    /// # Examples
+    ///
+    /// Cache entries for a day in the user's cache directory, as the binaries do:
+    ///
+    /// ```
+    /// use inspirer::cache::Cache;
+    /// use std::time::Duration;
+    ///
+    /// let cache = Cache::default_dir()
+    ///     .map(|dir| Cache::new(dir).with_ttl(Duration::from_secs(24 * 60 * 60)));
+    /// inspirer::Inspirer::init(None).with_cache(cache);
+    /// ```
+    pub fn with_cache(mut self, cache: Option<cache::Cache>) -> Self {
+        self.cache = cache;
+        self
+    }
+
+    /// Set how long cached entries are used for, keeping the cache directory
+    ///
+    /// This has no effect while caching is disabled.
+    pub fn with_cache_ttl(mut self, ttl: std::time::Duration) -> Self {
+        self.cache = self.cache.map(|cache| cache.with_ttl(ttl));
+        self
+    }
+
+    /// Work offline, taking entries only from the cache and local databases
+    ///
+    /// No network connections are made; keys whose entries are neither cached nor in a local
+    /// database (see `with_local_source`) fail with `InspirerError::Offline`.
+    ///
+    /// # Examples
+    /// ```
+    /// let inspirer = inspirer::Inspirer::init(None).with_offline(true);
+    ///
+    /// assert!(inspirer.bibtex("Randall:1999ee").is_err());
+    /// ```
+    pub fn with_offline(mut self, offline: bool) -> Self {
+        self.offline = offline;
+        self
+    }
+
+    /// Replace entries which are already in the output file by `put_output`, instead of skipping
+    /// them
+    pub fn with_replace(mut self, replace: bool) -> Self {
+        self.replace = replace;
+        self
+    }
+
+    /// Keep the previous version of the output file with `.bak` appended to its name whenever
+    /// `put_output` changes it
+    pub fn with_backup(mut self, backup: bool) -> Self {
+        self.backup = backup;
+        self
+    }
+
+    /// Lay out the entries written by `put_output` in a uniform format, or keep them as they were
+    /// fetched with `None`, the default
+    ///
+    /// # Examples
+    /// ```
+    /// use inspirer::format::{Case, Format};
+    ///
+    /// let format = Format::default().with_entry_case(Case::Upper);
+    /// inspirer::Inspirer::init(None).with_format(Some(format));
+    /// ```
+    pub fn with_format(mut self, format: Option<format::Format>) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Replace the AASTeX journal macros of ADS entries, such as `\apj`, by journal names
+    ///
+    /// By default the macros are kept.  See `journals::JOURNALS` for the macros which are known.
+    ///
+    /// # Examples
+    /// ```
+    /// use inspirer::journals::JournalNames;
+    ///
+    /// inspirer::Inspirer::init(None).with_journal_names(JournalNames::Abbreviated);
+    /// ```
+    pub fn with_journal_names(mut self, journal_names: journals::JournalNames) -> Self {
+        self.journal_names = journal_names;
+        self
+    }
+
+    /// Look records up with another client of the INSPIRE API, shared by the built-in sources
+    ///
+    /// # Examples
+    /// ```
+    /// use inspirer::inspire::Client;
+    /// use reqwest::Url;
+    ///
+    /// let client = Client::init(None).with_base_url(Url::parse("http://localhost:5000/api/").unwrap());
+    /// inspirer::Inspirer::init(None).with_inspire(client);
+    /// ```
+    pub fn with_inspire(mut self, client: inspire::Client) -> Self {
+        self.inspire = client;
+        self.replace_builtin();
+        self
+    }
+
+    /// Rebuild the built-in sources for the INSPIRE client and the policy
+    ///
+    /// The sources for arXiv and DOI keys ask INSPIRE first, which they must not do while the
+    /// policy disables it.
+    fn replace_builtin(&mut self) {
+        let ask_inspire = self.policy.is_enabled("inspire");
+        for builtin in source::builtin_asking_inspire(self.inspire.clone(), ask_inspire) {
+            if let Some(source) = self.sources.iter_mut().find(|s| s.name() == builtin.name()) {
+                *source = builtin;
+            }
+        }
+    }
+
+    /// Add a source of entries, after the built-in ones
+    ///
+    /// Each key is fetched from the source which recognises it with the highest confidence; of
+    /// equally confident sources, the one added first is used.  See `source` for an example.
+    pub fn with_source<S: source::Source + 'static>(mut self, source: S) -> Self {
+        self.sources.push(Box::new(source));
+        self
+    }
+
+    /// Take entries from local BibTeX databases before asking any other source
+    ///
+    /// See `source::Local`.  Local entries are used even when working offline.
+    pub fn with_local_source(mut self, local: source::Local) -> Self {
+        self.sources.insert(0, Box::new(local));
+        self
+    }
+
+    /// Set which sources are asked for which keys, see `policy`
+    ///
+    /// Sources the policy names which have not been added are ignored, with a warning.  Disabling
+    /// `inspire` also stops the sources for arXiv and DOI keys from asking INSPIRE first.
+    ///
+    /// # Examples
+    /// ```
+    /// use inspirer::policy::Policy;
+    ///
+    /// let inspirer = inspirer::Inspirer::init(None).with_policy(
+    ///     Policy::default()
+    ///         .with_order("ads=ads,inspire".parse().unwrap())
+    ///         .with_disabled("doi"),
+    /// );
+    ///
+    /// let labels = |key| -> Vec<&str> { inspirer.candidates(key).iter().map(|s| s.label()).collect() };
+    /// assert_eq!(labels("1999PhRvL..83.3370R"), vec!["ADS", "INSPIRE"]);
+    /// assert_eq!(labels("Randall:1999ee"), vec!["INSPIRE"]);
+    /// assert!(labels("10.1103/PhysRevLett.116.061102").is_empty());
+    /// ```
+    pub fn with_policy(mut self, policy: policy::Policy) -> Self {
+        for name in policy.names() {
+            if !self.sources.iter().any(|s| s.name() == name) {
+                warn!(self.logger, "Ignoring unknown source in policy"; "source" => name);
+            }
+        }
+        self.policy = policy;
+        self.replace_builtin();
+        self
+    }
+
+    /// The sources of entries, in the order they were added
+    pub fn sources(&self) -> &[Box<dyn source::Source>] {
+        &self.sources
+    }
+
+    /// The source `key` is fetched from, or `None` if no source recognises it
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let inspirer = inspirer::Inspirer::init(None);
+    ///
+    /// assert_eq!(inspirer.source("Randall:1999ee").unwrap().label(), "INSPIRE");
+    /// assert_eq!(inspirer.source("1999PhRvL..83.3370R").unwrap().label(), "ADS");
+    /// assert_eq!(inspirer.source("hep-th/9901001").unwrap().label(), "arXiv");
+    /// assert_eq!(inspirer.source("2103.01234v2").unwrap().label(), "arXiv");
+    /// assert_eq!(
+    ///     inspirer.source("10.1103/PhysRevLett.116.061102").unwrap().label(),
+    ///     "DOI"
+    /// );
+    /// assert!(inspirer.source("not a key").is_none());
+    /// ```
+    pub fn source(&self, key: &str) -> Option<&dyn source::Source> {
+        let mut best: Option<(source::Confidence, &dyn source::Source)> = None;
+        for source in &self.sources {
+            let confidence = source.recognises(key);
+            if confidence > best.map_or(source::Confidence::No, |(c, _)| c) {
+                best = Some((confidence, source.as_ref()));
+            }
+        }
+
+        best.map(|(_, source)| source)
+    }
+
+    /// The sources asked for `key`, in order, according to the policy
+    ///
+    /// Empty if no source recognises the key, or if all the sources that would be asked are
+    /// disabled.  With a fallback policy, disabling the source recognising the key leaves the
+    /// others to be asked.
+    pub fn candidates(&self, key: &str) -> Vec<&dyn source::Source> {
+        let class = match self.source(key) {
+            Some(source) => source.name(),
+            None => return Vec::new(),
+        };
+
+        let mut names: Vec<&str> = match self.policy.order(class) {
+            Some(order) => order.iter().map(|name| name.as_str()).collect(),
+            None => vec![class],
+        };
+        if self.policy.fallback() {
+            for source in &self.sources {
+                if !names.contains(&source.name()) {
+                    names.push(source.name());
+                }
+            }
         }
+
+        names
+            .into_iter()
+            .filter(|&name| self.policy.is_enabled(name))
+            .filter_map(|name| self.sources.iter().find(|s| s.name() == name))
+            .map(|source| source.as_ref())
+            .collect()
     }
 
     /// Read input from file or stdin
@@ -108,43 +487,132 @@ impl Inspirer {
     }
 
     /// Write output to file or stdout
+    ///
+    /// Entries are written as they were fetched, or laid out in the format set with
+    /// `with_format`.  The `@string` definitions and crossref parents entries depend on (see
+    /// `bib::Entry::dependencies`) are written verbatim beside them, unless already written or in
+    /// the file.
+    ///
+    /// Entries are appended to the file, except those whose keys are already in it, which are
+    /// skipped or, if set with `with_replace`, replaced in place.  Returns the keys of the skipped
+    /// entries.
+    ///
+    /// The file is replaced atomically, so it is never left half written, and other processes
+    /// writing to it with `put_output` are waited for.  To do so, a lock file is created beside it
+    /// with `.lock` appended to its name, and removed when done.  If the file is a symbolic link,
+    /// the file it points to is written.
     pub fn put_output(
         &self,
         output_dest: Option<&str>,
-        output: Vec<String>,
-    ) -> Result<(), InspirerError> {
-        let mut stdout = std::io::stdout();
-        let mut output_file: std::fs::File;
-
-        let writer: &mut dyn Write = match output_dest {
-            Some(file_name) => {
-                info!(self.logger, "Writing to file";
-                      "file_name" => file_name);
-                output_file = std::fs::OpenOptions::new()
-                    .append(true)
-                    .create(true)
-                    .open(file_name)
-                    .map_err(|e| InspirerError::Io(e))?;
-                &mut output_file
-            }
+        output: Vec<bib::Entry>,
+    ) -> Result<Vec<String>, InspirerError> {
+        let file_name = match output_dest {
+            Some(file_name) => file_name,
             None => {
                 info!(self.logger, "Writing to stdout");
-                // stdout.lock();
-                &mut stdout
+                let stdout = std::io::stdout();
+                let mut writer = BufWriter::new(stdout.lock());
+                let mut defined = Defined::default();
+                for entry in output {
+                    if defined.is_parent(entry.key()) {
+                        continue;
+                    }
+                    writer
+                        .write_all(self.entry_text(entry, &mut defined).as_bytes())
+                        .map_err(InspirerError::Io)?;
+                }
+                writer.flush().map_err(InspirerError::Io)?;
+
+                return Ok(Vec::new());
             }
         };
 
-        let mut writer = BufWriter::new(writer);
+        info!(self.logger, "Writing to file";
+              "file_name" => file_name);
+        let path = output::resolve(Path::new(file_name)).map_err(InspirerError::Io)?;
+        let _lock = output::lock(&self.logger, &path).map_err(InspirerError::Io)?;
+        let mut database = match std::fs::read_to_string(&path) {
+            Ok(database) => database,
+            Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(InspirerError::Io(e)),
+        };
+        let present: HashMap<String, Range<usize>> = bib::entries(&database)
+            .into_iter()
+            .map(|e| (e.key, e.span))
+            .collect();
+        let mut defined = Defined::in_database(&database);
+
+        let mut skipped = Vec::new();
+        let mut replacements = Vec::new();
+        let mut appended = Vec::new();
+        let mut written = HashSet::new();
+        for entry in output {
+            let key = entry.key().to_string();
+            if !written.insert(key.clone()) {
+                continue;
+            }
 
-        for o in output {
-            writer
-                .write_all(o.as_bytes())
-                .map_err(|e| InspirerError::Io(e))?;
+            match present.get(&key) {
+                Some(span) if self.replace => {
+                    info!(self.logger, "Replacing entry"; "key" => key);
+                    replacements.push((span.clone(), self.entry_text(entry, &mut defined)));
+                }
+                Some(_) => {
+                    info!(self.logger, "Skipping entry already in file"; "key" => key);
+                    skipped.push(key);
+                }
+                None if defined.is_parent(&key) => {}
+                None => appended.push(self.entry_text(entry, &mut defined)),
+            }
         }
 
-        writer.flush().map_err(|e| InspirerError::Io(e))?;
+        if replacements.is_empty() && appended.is_empty() {
+            return Ok(skipped);
+        }
 
-        Ok(())
+        // Replace from the end so that the earlier spans stay valid
+        replacements.sort_unstable_by_key(|(span, _)| std::cmp::Reverse(span.start));
+        for (span, entry) in replacements {
+            database.replace_range(span, entry.trim());
+        }
+        for o in appended {
+            // An entry must not run on from the last line of the file or of the previous entry
+            if !database.is_empty() && !database.ends_with('\n') {
+                database.push('\n');
+            }
+            database.push_str(&o);
+        }
+        output::replace(&path, &database, self.backup).map_err(InspirerError::Io)?;
+
+        Ok(skipped)
+    }
+
+    /// An entry as it is written by `put_output`, with the dependencies which are not `defined`
+    /// yet
+    fn entry_text(&self, entry: bib::Entry, defined: &mut Defined) -> String {
+        let dependencies = entry.dependencies().clone();
+
+        let mut text = String::new();
+        for (name, string) in &dependencies.strings {
+            if defined.strings.insert(name.to_lowercase()) {
+                text.push_str(string);
+                text.push('\n');
+            }
+        }
+        defined.keys.insert(entry.key().to_lowercase());
+        text.push_str(&match self.format {
+            Some(ref format) => format.format(&entry),
+            None => entry.into_string(),
+        });
+        for (key, parent) in &dependencies.parents {
+            if defined.keys.insert(key.to_lowercase()) {
+                defined.parents.insert(key.to_lowercase());
+                text.push_str(parent);
+                text.push('\n');
+            }
+        }
+
+        text
     }
 
     /// The `aux2key` function extracts TeX keys from LaTeX .aux files. These can be for either
@@ -191,164 +659,778 @@ impl Inspirer {
     ///
     /// assert_eq!(inspirer.aux2key(input), vec!("Cutler:1992tc"));
     /// ```
+    ///
+    /// Newer versions of biblatex also record the refsection:
+    ///
+    /// ```
+    /// let inspirer = inspirer::Inspirer::init(None);
+    ///
+    /// let input =
+    /// r"\relax
+    /// \abx@aux@refcontext{nty/global//global/global}
+    /// \abx@aux@cite{0}{Guth:1980zm}
+    /// \abx@aux@segm{0}{0}{Guth:1980zm}".to_string();
+    ///
+    /// assert_eq!(inspirer.aux2key(input), vec!("Guth:1980zm"));
+    /// ```
     pub fn aux2key(&self, input_data: String) -> Vec<String> {
-        lazy_static! {
-            // TODO: check on the exact characters allowed in keys
-            // Just find groups of keys which are cited together
-            static ref AUX_REGEX: Regex = Regex::new(
-                r"(?:\\citation|\\abx@aux@cite)\{(?P<key>.+)\}").expect("aux regex compiled during development");
-        }
-
-        lazy_static! {
-            // TODO: check on the exact characters allowed in keys
-            // Split keys which are cited together
-            static ref INNER_REGEX: Regex = Regex::new(
-                r"(?P<key>[^,]+),?").expect("aux regex compiled during development");
-        }
-
-        let mut matches: Vec<String> = AUX_REGEX
-            .captures_iter(&input_data)
-            .map(|c| c["key"].to_string())
-            .collect::<Vec<String>>()
-            .iter()
-            .flat_map(|s| INNER_REGEX.captures_iter(s).map(|c| c["key"].to_string()))
+        let mut keys: Vec<String> = self
+            .aux2citations(input_data)
+            .into_iter()
+            .map(|c| c.key)
             .collect();
 
-        // Deduplicate keys
-        // As a bonus, keys are sorted alphabetically
-        matches.sort_unstable();
-        matches.dedup();
+        // Citations are sorted by key, so this removes all duplicates
+        keys.dedup();
 
-        matches
+        keys
     }
 
-    /// The blg2key function extracts missing references from bibtex logs
+    /// The `aux2citations` function extracts citations from LaTeX .aux files, together with the
+    /// BibLaTeX refsection they belong to.
     ///
-    /// # Examples
+    /// See [`auxfile::AuxDocument`](auxfile/struct.AuxDocument.html) for everything else in the
+    /// file.
     ///
-    /// ADS-formatted BibTeX key:
+    /// Both the old (`\abx@aux@cite{<key>}`) and new (`\abx@aux@cite{<refsection>}{<key>}`)
+    /// biblatex syntax are understood.  With the old syntax the refsection is taken from the
+    /// matching `\abx@aux@segm` lines.
+    ///
+    /// # Examples
     ///
     /// ```
+    /// use inspirer::AuxCitation;
+    ///
     /// let inspirer = inspirer::Inspirer::init(None);
     ///
     /// let input =
-    /// r##"
-    /// This is BibTeX, Version 0.99d (TeX Live 2016/Arch Linux)
-    /// Capacity: max_strings=35307, hash_size=35307, hash_prime=30011
-    /// The top-level auxiliary file: test_bibtex.aux
-    /// The style file: unsrt.bst
-    /// Database file #1: test_bibtex.bib
-    /// Warning--I didn't find a database entry for "2015CQGra..32g4001L"
-    /// You've used 0 entries,
-    /// ....
-    /// "##.to_string();
+    /// r"\relax
+    /// \citation{Abramovici:1992ah}
+    /// \abx@aux@cite{1}{Guth:1980zm}".to_string();
     ///
-    /// assert_eq!(inspirer.blg2key(input), vec!("2015CQGra..32g4001L"));
+    /// assert_eq!(
+    ///     inspirer.aux2citations(input),
+    ///     vec![
+    ///         AuxCitation { key: "Abramovici:1992ah".to_string(), refsection: None },
+    ///         AuxCitation { key: "Guth:1980zm".to_string(), refsection: Some(1) },
+    ///     ]
+    /// );
     /// ```
-    pub fn blg2key(&self, input_data: String) -> Vec<String> {
-        lazy_static! {
-            static ref BLG_REGEX: Regex = Regex::new(
-                r#"(Warning--|WARN - )I didn't find a database entry for ["'](.+)["']"#,
-            )
-            .expect("blg regex compiled during development");
-        }
-
-        let mut matches: Vec<String> = BLG_REGEX
-            .captures_iter(&input_data)
-            .map(|c| c.get(2).unwrap().as_str().to_string())
+    pub fn aux2citations(&self, input_data: String) -> Vec<AuxCitation> {
+        let mut citations: Vec<AuxCitation> = auxfile::AuxDocument::parse(&input_data)
+            .citations
+            .into_iter()
+            .map(|c| AuxCitation {
+                key: c.key,
+                refsection: c.refsection,
+            })
             .collect();
 
-        matches.sort_unstable();
-        matches.dedup();
+        // Deduplicate citations
+        // As a bonus, citations are sorted alphabetically by key
+        citations.sort_unstable();
+        citations.dedup();
 
-        matches
+        citations
     }
 
-    /// Fetch BibTeX entries
-    pub fn bibtex(&self, key: &str) -> Option<String> {
-        let key = Sources::from(key);
+    /// The `aux_file2key` function extracts TeX keys from an .aux file and from every .aux file
+    /// it includes with `\@input`, as LaTeX writes for documents using `\include`.
+    ///
+    /// Included files are resolved relative to the directory of the top-level file.  Each key is
+    /// returned once, together with the first file it was found in.  Files which are included
+    /// more than once, or which include themselves, are only read once.
+    pub fn aux_file2key(&self, path: &Path) -> Result<Vec<KeyOrigin>, InspirerError> {
+        self.file2key(path, &|input_data| {
+            let document = auxfile::AuxDocument::parse(input_data);
+            let inputs = document.inputs.iter().map(PathBuf::from).collect();
+
+            (document.keys(), inputs)
+        })
+    }
+
+    /// The `aux_file2missing` function extracts TeX keys like `aux_file2key`, leaving out those
+    /// which are already in the databases named by `\bibdata` in any of the .aux files read.
+    ///
+    /// As for BibTeX, `.bib` is added to database names which lack it, and keys are compared
+    /// without regard to case.  Databases are resolved relative to the directory of the top-level
+    /// .aux file; those which cannot be read are taken to be empty.
+    pub fn aux_file2missing(&self, path: &Path) -> Result<Vec<KeyOrigin>, InspirerError> {
+        let databases: RefCell<Vec<String>> = RefCell::new(Vec::new());
+        let origins = self.file2key(path, &|input_data| {
+            let document = auxfile::AuxDocument::parse(input_data);
+            let inputs = document.inputs.iter().map(PathBuf::from).collect();
 
-        match key {
-            Sources::Inspire(k) => {
-                debug!(self.logger, "Record type: Inspire"; "key" => k.id);
-                self.inspire.fetch_bibtex_with_key(k)
+            let mut databases = databases.borrow_mut();
+            for database in &document.bibdata {
+                if !databases.contains(database) {
+                    databases.push(database.clone());
+                }
             }
-            Sources::Ads(k) => {
-                debug!(self.logger, "Record type: ADS"; "key" => k.bibcode);
-                self.ads.fetch_bibtex_with_key(k)
+
+            (document.keys(), inputs)
+        })?;
+
+        let databases = databases.into_inner();
+        if databases.is_empty() {
+            warn!(self.logger, "No databases named by \\bibdata";
+                  "file_name" => path.display().to_string());
+        }
+
+        let base = path.parent().unwrap_or_else(|| Path::new(""));
+        let mut present: HashSet<String> = HashSet::new();
+        for database in databases {
+            let mut database_path = base.join(&database);
+            if database_path.extension() != Some(std::ffi::OsStr::new("bib")) {
+                database_path = base.join(database + ".bib");
             }
-            _ => {
-                // debug!(self.logger, "Unknown record source"; "key" => key);
-                debug!(self.logger, "Record type: unknown");
-                None
+
+            match std::fs::read_to_string(&database_path) {
+                Ok(data) => present.extend(
+                    bib::entries(&data)
+                        .into_iter()
+                        .map(|e| e.key.to_lowercase()),
+                ),
+                Err(e) => warn!(self.logger, "Unable to read database";
+                                "file_name" => database_path.display().to_string(),
+                                "error" => e.to_string()),
             }
         }
+        debug!(self.logger, "Read keys from databases";
+               "number_of_keys" => present.len());
+
+        Ok(origins
+            .into_iter()
+            .filter(|origin| !present.contains(&origin.key.to_lowercase()))
+            .collect())
     }
-}
 
-#[derive(Debug, PartialEq)]
-pub enum Sources<'a> {
-    Inspire(libinspire::RecID<'a>),
-    Ads(libads::BibCode<'a>),
-    Arxiv,
-    None,
-}
+    /// The `tex2key` function extracts TeX keys from LaTeX source.
+    ///
+    /// Citation commands from LaTeX, natbib and biblatex are recognised, and commented-out
+    /// citations are ignored.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let inspirer = inspirer::Inspirer::init(None);
+    ///
+    /// let input =
+    /// r"As shown in \citep[p.~3]{Abramovici:1992ah}
+    /// and \textcites[see][]{1998PhRvD..58h4020O}{Cutler:1992tc}
+    /// % \cite{Thorne:1992sdb}".to_string();
+    ///
+    /// assert_eq!(
+    ///     inspirer.tex2key(input),
+    ///     vec!("1998PhRvD..58h4020O", "Abramovici:1992ah", "Cutler:1992tc")
+    /// );
+    /// ```
+    pub fn tex2key(&self, input_data: String) -> Vec<String> {
+        tex::TexDocument::parse(&input_data).keys()
+    }
 
-/// Guess a likely source for a BibTeX key
-///
-/// Returns `Sources::None` if unable to make a good guess.
-///
-/// # Examples
-/// ```
-/// extern crate inspirer;
-/// extern crate libinspire;
-/// let inspirer = inspirer::Inspirer::init(None);
-///
-/// assert_eq!(
-///     inspirer::Sources::from("Randall:1999ee"),
-///     inspirer::Sources::Inspire(libinspire::RecID::new("Randall:1999ee").unwrap())
-/// );
-/// ```
-///
-/// ```
-/// extern crate inspirer;
-/// extern crate libads;
-/// let inspirer = inspirer::Inspirer::init(None);
-///
-/// assert_eq!(
-///     inspirer::Sources::from("1999PhRvL..83.3370R"),
-///     inspirer::Sources::Ads(libads::BibCode::new("1999PhRvL..83.3370R").unwrap())
-/// );
-/// ```
-impl<'a> From<&'a str> for Sources<'a> {
-    fn from(s: &'a str) -> Sources<'a> {
-        if libinspire::validate_recid(s) {
-            Sources::Inspire(libinspire::RecID::new(s).unwrap())
-        } else if libads::validate_bib_code(s) {
-            Sources::Ads(libads::BibCode::new(s).unwrap())
-        } else {
-            Sources::None
-        }
+    /// The `tex_file2key` function extracts TeX keys from a LaTeX source file and from every
+    /// file it includes with `\input` or `\include`.
+    ///
+    /// Included files are resolved relative to the directory of the top-level file, adding a
+    /// `.tex` extension where none is given.  Each key is returned once, together with the first
+    /// file it was found in.
+    pub fn tex_file2key(&self, path: &Path) -> Result<Vec<KeyOrigin>, InspirerError> {
+        self.file2key(path, &|input_data| {
+            let document = tex::TexDocument::parse(input_data);
+            let inputs = document
+                .inputs
+                .iter()
+                .map(|input| {
+                    let input = PathBuf::from(input);
+                    if input.extension().is_none() {
+                        input.with_extension("tex")
+                    } else {
+                        input
+                    }
+                })
+                .collect();
+
+            (document.keys(), inputs)
+        })
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// Extract keys from a file and, recursively, from the files it includes
+    ///
+    /// `parse` returns the keys of a file together with the files it includes.
+    fn file2key(
+        &self,
+        path: &Path,
+        parse: &KeyParser<'_>,
+    ) -> Result<Vec<KeyOrigin>, InspirerError> {
+        let base = path.parent().unwrap_or_else(|| Path::new(""));
 
-    #[test]
-    fn test_aux_bibtex_0_citations() {
-        let input = r"\relax ".to_string();
+        let mut origins: Vec<KeyOrigin> = Vec::new();
+        let mut visited: Vec<PathBuf> = Vec::new();
+        self.collect_keys(path, base, parse, &mut visited, &mut origins, true)?;
 
-        let output: Vec<String> = Vec::new();
-        assert_eq!(Inspirer::init(None).aux2key(input), output);
+        origins.sort_unstable();
+
+        Ok(origins)
     }
 
-    #[test]
-    fn test_aux_bibtex_1_citation() {
-        let input = r"\relax
-            \citation{Abramovici:1992ah}"
-            .to_string();
+    fn collect_keys(
+        &self,
+        path: &Path,
+        base: &Path,
+        parse: &KeyParser<'_>,
+        visited: &mut Vec<PathBuf>,
+        origins: &mut Vec<KeyOrigin>,
+        top_level: bool,
+    ) -> Result<(), InspirerError> {
+        let canonical = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        if visited.contains(&canonical) {
+            warn!(self.logger, "Skipping file which has already been read";
+                  "file_name" => path.display().to_string());
+            return Ok(());
+        }
+        visited.push(canonical);
+
+        info!(self.logger, "Reading from file";
+              "file_name" => path.display().to_string());
+        let input_data = match std::fs::read_to_string(path) {
+            Ok(input_data) => input_data,
+            Err(e) => {
+                if top_level {
+                    return Err(InspirerError::Io(e));
+                }
+                // LaTeX only writes the child .aux files on its next run
+                warn!(self.logger, "Unable to read included file";
+                      "file_name" => path.display().to_string(),
+                      "error" => e.to_string());
+                return Ok(());
+            }
+        };
+
+        let (keys, inputs) = parse(&input_data);
+
+        // Keep the order of the file so that earlier files are reported as the origin
+        let position = origins.len();
+        for key in keys {
+            if !origins.iter().any(|o| o.key == key) {
+                origins.push(KeyOrigin {
+                    key,
+                    file: path.to_path_buf(),
+                });
+            }
+        }
+        debug!(self.logger, "Extracted BibTeX keys";
+               "file_name" => path.display().to_string(),
+               "number_of_keys" => origins.len() - position);
+
+        for input in inputs {
+            self.collect_keys(&base.join(input), base, parse, visited, origins, false)?;
+        }
+
+        Ok(())
+    }
+
+    /// The `bcf2key` function extracts TeX keys from the control file biblatex writes for biber
+    ///
+    /// The keys of all refsections are returned together; use `bcf::BcfDocument` for the keys of
+    /// each section and the declared data sources.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let inspirer = inspirer::Inspirer::init(None);
+    ///
+    /// let input =
+    /// r#"<bcf:controlfile version="3.7" xmlns:bcf="https://sourceforge.net/projects/biblatex">
+    ///   <bcf:section number="0">
+    ///     <bcf:citekey order="1">Abramovici:1992ah</bcf:citekey>
+    ///     <bcf:citekey order="2">1998PhRvD..58h4020O</bcf:citekey>
+    ///   </bcf:section>
+    /// </bcf:controlfile>"#.to_string();
+    ///
+    /// assert_eq!(
+    ///     inspirer.bcf2key(input),
+    ///     vec!("1998PhRvD..58h4020O", "Abramovici:1992ah")
+    /// );
+    /// ```
+    pub fn bcf2key(&self, input_data: String) -> Vec<String> {
+        bcf::BcfDocument::parse(&input_data).keys()
+    }
+
+    /// The blg2key function extracts missing references from bibtex logs
+    ///
+    /// # Examples
+    ///
+    /// ADS-formatted BibTeX key:
+    ///
+    /// ```
+    /// let inspirer = inspirer::Inspirer::init(None);
+    ///
+    /// let input =
+    /// r##"
+    /// This is BibTeX, Version 0.99d (TeX Live 2016/Arch Linux)
+    /// Capacity: max_strings=35307, hash_size=35307, hash_prime=30011
+    /// The top-level auxiliary file: test_bibtex.aux
+    /// The style file: unsrt.bst
+    /// Database file #1: test_bibtex.bib
+    /// Warning--I didn't find a database entry for "2015CQGra..32g4001L"
+    /// You've used 0 entries,
+    /// ....
+    /// "##.to_string();
+    ///
+    /// assert_eq!(inspirer.blg2key(input), vec!("2015CQGra..32g4001L"));
+    /// ```
+    pub fn blg2key(&self, input_data: String) -> Vec<String> {
+        self.blg2report(input_data).missing_keys()
+    }
+
+    /// The `blg2report` function collects every warning and error from bibtex and biber logs
+    ///
+    /// See [`blg::BlgReport`](blg/struct.BlgReport.html) for how they are classified.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use inspirer::blg::Problem;
+    ///
+    /// let inspirer = inspirer::Inspirer::init(None);
+    ///
+    /// let input =
+    /// r#"Database file #1: test_bibtex.bib
+    /// Warning--I didn't find a database entry for "2015CQGra..32g4001L"
+    /// Warning--empty journal in Abramovici:1992ah"#.to_string();
+    ///
+    /// let report = inspirer.blg2report(input);
+    /// assert_eq!(
+    ///     report.messages[1].problem,
+    ///     Problem::MissingField {
+    ///         key: "Abramovici:1992ah".to_string(),
+    ///         field: "journal".to_string(),
+    ///     }
+    /// );
+    /// ```
+    pub fn blg2report(&self, input_data: String) -> blg::BlgReport {
+        blg::BlgReport::parse(&input_data)
+    }
+
+    /// The log2key function extracts undefined citations from LaTeX logs
+    ///
+    /// The warnings of LaTeX, natbib and biblatex are recognised, including those which TeX has
+    /// wrapped across lines.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let inspirer = inspirer::Inspirer::init(None);
+    ///
+    /// let input =
+    /// r##"
+    /// LaTeX Warning: Citation `Abramovici:1992ah' on page 1 undefined on input line 5.
+    ///
+    /// Package natbib Warning: Citation `1998PhRvD..58h4020O' on page 1 undefined on input line 6.
+    /// "##.to_string();
+    ///
+    /// assert_eq!(
+    ///     inspirer.log2key(input),
+    ///     vec!("1998PhRvD..58h4020O", "Abramovici:1992ah")
+    /// );
+    /// ```
+    pub fn log2key(&self, input_data: String) -> Vec<String> {
+        lazy_static! {
+            static ref LOG_REGEX: Regex = Regex::new(
+                r"(?:LaTeX|Package natbib) Warning: Citation [`'](?P<key>[^'\s]+)' (?:on page \S+ )?undefined",
+            )
+            .expect("log regex compiled during development");
+            static ref BIBLATEX_REGEX: Regex = Regex::new(
+                r"Package biblatex Warning: The following entry could not be found\s*\n\(biblatex\)\s+in the database:\s*\n\(biblatex\)\s+(?P<key>\S+)",
+            )
+            .expect("log regex compiled during development");
+        }
+
+        let input_data = unwrap_log_lines(&input_data);
+
+        let mut matches: Vec<String> = LOG_REGEX
+            .captures_iter(&input_data)
+            .chain(BIBLATEX_REGEX.captures_iter(&input_data))
+            .map(|c| c["key"].to_string())
+            .collect();
+
+        matches.sort_unstable();
+        matches.dedup();
+
+        matches
+    }
+
+    /// Fetch the BibTeX entries of many keys concurrently
+    ///
+    /// At most `concurrency` requests (see `with_concurrency`) are sent to each host at once, even
+    /// when several sources ask the same host, as the arXiv and DOI sources ask INSPIRE.  Keys are
+    /// shared between workers by the service of the source recognising them, so sources which do
+    /// not send their requests through this crate are also held to `concurrency` requests at
+    /// once.  The results are returned in the order of `keys`.
+    ///
+    /// # Examples
+    /// ```
+    /// use inspirer::InspirerError;
+    ///
+    /// let inspirer = inspirer::Inspirer::init(None);
+    ///
+    /// for result in inspirer.bibtex_many(&["not a key", "also not a key"]) {
+    ///     match result {
+    ///         Err(InspirerError::UnknownKeyFormat { .. }) => {}
+    ///         _ => panic!("keys are not recognised"),
+    ///     }
+    /// }
+    /// ```
+    pub fn bibtex_many(&self, keys: &[&str]) -> Vec<Result<bib::Entry, InspirerError>> {
+        self.bibtex_many_with_source(keys)
+            .into_iter()
+            .map(|result| result.map(|(entry, _)| entry))
+            .collect()
+    }
+
+    /// Fetch the BibTeX entries of many keys concurrently, with the names of the sources they came
+    /// from
+    ///
+    /// See `bibtex_many` and `bibtex_with_source`.  The results can be summarised by
+    /// `report::Report::new`.
+    pub fn bibtex_many_with_source(
+        &self,
+        keys: &[&str],
+    ) -> Vec<Result<(bib::Entry, &'static str), InspirerError>> {
+        // Positions of the keys fetched from each service
+        let mut queues: Vec<(&str, Vec<usize>)> = Vec::new();
+        for (i, key) in keys.iter().enumerate() {
+            let service = self
+                .candidates(key)
+                .first()
+                .map_or("", |source| source.service());
+            match queues.iter_mut().find(|(s, _)| *s == service) {
+                Some((_, queue)) => queue.push(i),
+                None => queues.push((service, vec![i])),
+            }
+        }
+        let queues: Vec<(Vec<usize>, AtomicUsize)> = queues
+            .into_iter()
+            .map(|(_, queue)| (queue, AtomicUsize::new(0)))
+            .collect();
+
+        let mut results: Vec<Option<Result<(bib::Entry, &'static str), InspirerError>>> =
+            Vec::new();
+        results.resize_with(keys.len(), || None);
+        let results = Mutex::new(results);
+
+        thread::scope(|scope| {
+            let results = &results;
+            for (queue, next) in &queues {
+                for _ in 0..self.concurrency.min(queue.len()) {
+                    scope.spawn(move || {
+                        self.limits.apply(|| {
+                            // Each worker takes the next key of its service until none are left
+                            while let Some(&i) = queue.get(next.fetch_add(1, Ordering::SeqCst)) {
+                                let result = self.bibtex_with_source(keys[i]);
+                                results
+                                    .lock()
+                                    .expect("no worker panics while holding the lock")[i] =
+                                    Some(result);
+                            }
+                        })
+                    });
+                }
+            }
+        });
+
+        results
+            .into_inner()
+            .expect("no worker panics while holding the lock")
+            .into_iter()
+            .map(|result| result.expect("every key is fetched by a worker"))
+            .collect()
+    }
+
+    /// Fetch BibTeX entries
+    ///
+    /// Entries are taken from the cache if possible, and added to it otherwise.  Offline (see
+    /// `with_offline`), only the cache and local databases are used.
+    ///
+    /// The text of the entry as it was fetched is kept, see `bib::Entry::raw`.
+    ///
+    /// # Examples
+    /// ```
+    /// use inspirer::InspirerError;
+    ///
+    /// let inspirer = inspirer::Inspirer::init(None);
+    ///
+    /// match inspirer.bibtex("not a key") {
+    ///     Err(InspirerError::UnknownKeyFormat { key }) => assert_eq!(key, "not a key"),
+    ///     _ => panic!("key is not recognised"),
+    /// }
+    /// ```
+    pub fn bibtex(&self, key: &str) -> Result<bib::Entry, InspirerError> {
+        self.bibtex_with_source(key).map(|(entry, _)| entry)
+    }
+
+    /// Fetch BibTeX entries, with the name of the source they came from
+    ///
+    /// With a fallback policy (see `policy`), the source which answered need not be the one
+    /// recognising the key.
+    ///
+    /// # Examples
+    /// ```
+    /// let inspirer = inspirer::Inspirer::init(None);
+    ///
+    /// assert!(inspirer.bibtex_with_source("not a key").is_err());
+    /// ```
+    pub fn bibtex_with_source(
+        &self,
+        key: &str,
+    ) -> Result<(bib::Entry, &'static str), InspirerError> {
+        let sources = self.candidates(key);
+        if sources.is_empty() {
+            debug!(self.logger, "Record type: unknown"; "key" => key);
+            return Err(InspirerError::UnknownKeyFormat {
+                key: key.to_string(),
+            });
+        }
+
+        // Local databases asked before any server are preferred to cached entries, which may be
+        // stale
+        let first_remote = sources.iter().position(|s| !s.local());
+
+        // The first source's error is the most telling, unless it could not handle the key
+        let mut error: Option<InspirerError> = None;
+        for (i, &source) in sources.iter().enumerate() {
+            if Some(i) == first_remote {
+                if let Some(cached) = self.cached(&sources[i..], key) {
+                    return Ok(cached);
+                }
+            }
+
+            debug!(self.logger, "Record type"; "key" => key, "source" => source.label());
+            let result = if self.offline && !source.local() {
+                Err(InspirerError::Offline {
+                    key: key.to_string(),
+                })
+            } else {
+                self.fetch(source, key)
+            };
+            match result {
+                Ok(entry) => return Ok((entry, source.name())),
+                Err(e) => {
+                    debug!(self.logger, "Source failed";
+                           "key" => key, "source" => source.label(), "error" => e.to_string());
+                    match error {
+                        None | Some(InspirerError::UnknownKeyFormat { .. }) => error = Some(e),
+                        Some(_) => {}
+                    }
+                }
+            }
+        }
+
+        Err(error.expect("at least one source is asked"))
+    }
+
+    /// The first cached entry for `key` from any of `sources` other than local databases
+    fn cached(
+        &self,
+        sources: &[&dyn source::Source],
+        key: &str,
+    ) -> Option<(bib::Entry, &'static str)> {
+        let cache = self.cache.as_ref()?;
+        for source in sources.iter().filter(|s| !s.local()) {
+            if let Some(text) = cache.get(source.name(), key) {
+                match self.parse(&text, source.journal_macros()) {
+                    Ok(entry) => {
+                        debug!(self.logger, "Using cached entry";
+                               "key" => key, "source" => source.label());
+                        return Some((entry, source.name()));
+                    }
+                    Err(e) => warn!(self.logger, "Ignoring invalid cached entry";
+                                    "key" => key, "error" => e.to_string()),
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Fetch an entry from one source, caching it
+    fn fetch(&self, source: &dyn source::Source, key: &str) -> Result<bib::Entry, InspirerError> {
+        let text = source.fetch(key)?;
+        // Journal macros are only expanded after caching, so that the option can be changed
+        let entry = if source.local() {
+            bib::Entry::parse_with_dependencies(&text)
+        } else {
+            self.parse(&text, source.journal_macros())
+        };
+        let entry = entry.map_err(|e| InspirerError::InvalidEntry {
+            key: key.to_string(),
+            reason: e.to_string(),
+        })?;
+
+        if let Some(cache) = self.cache.as_ref().filter(|_| !source.local()) {
+            if let Err(e) = cache.put(source.name(), key, &text) {
+                warn!(self.logger, "Unable to cache entry";
+                      "key" => key, "error" => e.to_string());
+            }
+        }
+
+        Ok(entry)
+    }
+
+    fn parse(&self, text: &str, ads: bool) -> Result<bib::Entry, bib::ParseError> {
+        if ads && self.journal_names != journals::JournalNames::Macros {
+            bib::Entry::parse(&journals::expand(text, self.journal_names))
+        } else {
+            bib::Entry::parse(text)
+        }
+    }
+}
+
+/// The abbreviations and keys defined in the output of `put_output`, lowercased as BibTeX
+/// matches them ignoring case, so that the dependencies of entries are written once
+#[derive(Default)]
+struct Defined {
+    strings: HashSet<String>,
+    keys: HashSet<String>,
+    /// Keys of the entries written as crossref parents of others
+    parents: HashSet<String>,
+}
+
+impl Defined {
+    fn in_database(database: &str) -> Self {
+        Defined {
+            strings: bib::string_spans(database)
+                .into_iter()
+                .map(|string| string.name.to_lowercase())
+                .collect(),
+            keys: bib::entries(database)
+                .into_iter()
+                .map(|entry| entry.key.to_lowercase())
+                .collect(),
+            parents: HashSet::new(),
+        }
+    }
+
+    /// Whether `key` was already written as the crossref parent of another entry
+    fn is_parent(&self, key: &str) -> bool {
+        self.parents.contains(&key.to_lowercase())
+    }
+}
+
+/// A citation found in an .aux file
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct AuxCitation {
+    pub key: String,
+    /// The BibLaTeX refsection, or `None` for BibTeX citations
+    pub refsection: Option<u32>,
+}
+
+/// The source a key is fetched from
+///
+/// Superseded by the sources of an `Inspirer`, which can be extended with `with_source`.  INSPIRE
+/// keys are kept as they were cited, since records are no longer looked up with `libinspire`.
+#[deprecated(
+    since = "0.2.0",
+    note = "use `Inspirer::source`, which also knows the sources added with `with_source`"
+)]
+#[derive(Debug, PartialEq)]
+pub enum Sources<'a> {
+    Inspire(&'a str),
+    Ads(libads::BibCode<'a>),
+    Arxiv(arxiv::ArxivId<'a>),
+    Doi(doi::Doi<'a>),
+    None,
+}
+
+/// Guess a likely source for a BibTeX key, as the built-in sources recognise it
+///
+/// Returns `Sources::None` if unable to make a good guess.
+///
+/// # Examples
+/// ```
+/// # #![allow(deprecated)]
+/// use inspirer::doi::Doi;
+///
+/// assert_eq!(inspirer::Sources::from("Randall:1999ee"), inspirer::Sources::Inspire("Randall:1999ee"));
+/// assert_eq!(
+///     inspirer::Sources::from("10.1103/PhysRevLett.116.061102"),
+///     inspirer::Sources::Doi(Doi::new("10.1103/PhysRevLett.116.061102").unwrap())
+/// );
+/// assert_eq!(inspirer::Sources::from("not a key"), inspirer::Sources::None);
+/// ```
+#[allow(deprecated)]
+impl<'a> From<&'a str> for Sources<'a> {
+    fn from(s: &'a str) -> Sources<'a> {
+        let name = match Inspirer::init(None).source(s) {
+            Some(source) => source.name(),
+            None => return Sources::None,
+        };
+
+        // The built-in sources only recognise keys these parse
+        match name {
+            "inspire" => Sources::Inspire(s),
+            "ads" => libads::BibCode::new(s).map_or(Sources::None, Sources::Ads),
+            "arxiv" => arxiv::ArxivId::new(s).map_or(Sources::None, Sources::Arxiv),
+            "doi" => doi::Doi::new(s).map_or(Sources::None, Sources::Doi),
+            _ => Sources::None,
+        }
+    }
+}
+
+/// A cited key together with the file it was found in
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct KeyOrigin {
+    pub key: String,
+    pub file: PathBuf,
+}
+
+/// Extracts the keys cited in a file, together with the files it includes
+type KeyParser<'a> = dyn Fn(&str) -> (Vec<String>, Vec<PathBuf>) + 'a;
+
+/// Undo the line breaks TeX inserts into citation warnings in its log
+///
+/// TeX breaks every line of the log after `max_print_line` characters, 79 by default.  A line of
+/// exactly that length is only joined to the next while it is part of a citation warning which has
+/// not got as far as "undefined", so that other lines which happen to be that long are kept apart.
+fn unwrap_log_lines(log: &str) -> String {
+    const MAX_PRINT_LINE: usize = 79;
+
+    let mut unwrapped = String::with_capacity(log.len());
+    // Start of the line being unwrapped
+    let mut start = 0;
+    for line in log.lines() {
+        unwrapped.push_str(line);
+        let current = &unwrapped[start..];
+        let unfinished = current
+            .find("Warning: Citation")
+            .is_some_and(|i| !current[i..].contains(" undefined"));
+        if line.len() != MAX_PRINT_LINE || !unfinished {
+            unwrapped.push('\n');
+            start = unwrapped.len();
+        }
+    }
+
+    unwrapped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_aux_bibtex_0_citations() {
+        let input = r"\relax ".to_string();
+
+        let output: Vec<String> = Vec::new();
+        assert_eq!(Inspirer::init(None).aux2key(input), output);
+    }
+
+    #[test]
+    fn test_aux_bibtex_1_citation() {
+        let input = r"\relax
+            \citation{Abramovici:1992ah}"
+            .to_string();
 
         assert_eq!(
             Inspirer::init(None).aux2key(input),
@@ -421,5 +1503,859 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_aux_biblatex_old_syntax() {
+        let input = r"\relax
+            \abx@aux@sortscheme{nty}
+            \abx@aux@refcontext{nty/global/}
+            \abx@aux@cite{Guth:1980zm}
+            \abx@aux@segm{0}{0}{Guth:1980zm}
+            \abx@aux@cite{1982PhRvL..48.1220A}
+            \abx@aux@segm{0}{0}{1982PhRvL..48.1220A}"
+            .to_string();
+
+        assert_eq!(
+            Inspirer::init(None).aux2key(input),
+            vec!["1982PhRvL..48.1220A", "Guth:1980zm"]
+        );
+    }
+
+    #[test]
+    fn test_aux_biblatex_new_syntax() {
+        let input = r"\relax
+            \abx@aux@refcontext{nty/global//global/global}
+            \abx@aux@cite{0}{Guth:1980zm}
+            \abx@aux@segm{0}{0}{Guth:1980zm}
+            \abx@aux@cite{0}{Linde:1981mu}
+            \abx@aux@segm{0}{0}{Linde:1981mu}"
+            .to_string();
+
+        assert_eq!(
+            Inspirer::init(None).aux2key(input),
+            vec!["Guth:1980zm", "Linde:1981mu"]
+        );
+    }
+
+    #[test]
+    fn test_aux_biblatex_refsections() {
+        let input = r"\relax
+            \abx@aux@cite{0}{Guth:1980zm}
+            \abx@aux@segm{0}{0}{Guth:1980zm}
+            \abx@aux@cite{1}{Guth:1980zm}
+            \abx@aux@segm{1}{0}{Guth:1980zm}
+            \abx@aux@cite{2}{Linde:1981mu}"
+            .to_string();
+
+        let inspirer = Inspirer::init(None);
+
+        assert_eq!(
+            inspirer.aux2citations(input.clone()),
+            vec![
+                AuxCitation {
+                    key: "Guth:1980zm".to_string(),
+                    refsection: Some(0)
+                },
+                AuxCitation {
+                    key: "Guth:1980zm".to_string(),
+                    refsection: Some(1)
+                },
+                AuxCitation {
+                    key: "Linde:1981mu".to_string(),
+                    refsection: Some(2)
+                },
+            ]
+        );
+        assert_eq!(inspirer.aux2key(input), vec!["Guth:1980zm", "Linde:1981mu"]);
+    }
+
+    #[test]
+    fn test_aux_biblatex_old_syntax_refsection_from_segm() {
+        let input = r"\relax
+            \abx@aux@cite{Guth:1980zm}
+            \abx@aux@segm{3}{0}{Guth:1980zm}
+            \abx@aux@cite{Linde:1981mu}"
+            .to_string();
+
+        assert_eq!(
+            Inspirer::init(None).aux2citations(input),
+            vec![
+                AuxCitation {
+                    key: "Guth:1980zm".to_string(),
+                    refsection: Some(3)
+                },
+                AuxCitation {
+                    key: "Linde:1981mu".to_string(),
+                    refsection: Some(0)
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_aux_file_includes() {
+        let tmp_dir = assert_fs::TempDir::new().expect("can create tmp_dir");
+        std::fs::write(
+            tmp_dir.path().join("thesis.aux"),
+            "\\relax\n\\citation{Higgs:2014aqa}\n\\@input{chap1.aux}\n\\@input{chap2.aux}\n",
+        )
+        .unwrap();
+        std::fs::write(
+            tmp_dir.path().join("chap1.aux"),
+            "\\relax\n\\citation{Guth:1980zm,Higgs:2014aqa}\n",
+        )
+        .unwrap();
+        std::fs::write(
+            tmp_dir.path().join("chap2.aux"),
+            "\\relax\n\\citation{Linde:1981mu}\n",
+        )
+        .unwrap();
+
+        let origins = Inspirer::init(None)
+            .aux_file2key(&tmp_dir.path().join("thesis.aux"))
+            .unwrap();
+
+        assert_eq!(
+            origins,
+            vec![
+                KeyOrigin {
+                    key: "Guth:1980zm".to_string(),
+                    file: tmp_dir.path().join("chap1.aux"),
+                },
+                KeyOrigin {
+                    key: "Higgs:2014aqa".to_string(),
+                    file: tmp_dir.path().join("thesis.aux"),
+                },
+                KeyOrigin {
+                    key: "Linde:1981mu".to_string(),
+                    file: tmp_dir.path().join("chap2.aux"),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_aux_file_missing_only() {
+        let tmp_dir = assert_fs::TempDir::new().expect("can create tmp_dir");
+        std::fs::write(
+            tmp_dir.path().join("paper.aux"),
+            "\\citation{Higgs:2014aqa,Higgs:2015mei}\n\\@input{chap1.aux}\n\\bibdata{refs,old.bib,missing}\n",
+        )
+        .unwrap();
+        std::fs::write(
+            tmp_dir.path().join("chap1.aux"),
+            "\\citation{Guth:1980zm}\n\\citation{Linde:1981mu}\n",
+        )
+        .unwrap();
+        std::fs::write(
+            tmp_dir.path().join("refs.bib"),
+            "@article{Higgs:2014aqa,\n}\n",
+        )
+        .unwrap();
+        std::fs::write(tmp_dir.path().join("old.bib"), "@article{Guth:1980zm,\n}\n").unwrap();
+
+        let inspirer = Inspirer::init(None);
+        let keys: Vec<String> = inspirer
+            .aux_file2missing(&tmp_dir.path().join("paper.aux"))
+            .unwrap()
+            .into_iter()
+            .map(|origin| origin.key)
+            .collect();
+
+        assert_eq!(keys, vec!["Higgs:2015mei", "Linde:1981mu"]);
+    }
+
+    #[test]
+    fn test_aux_file_missing_only_included_bibdata() {
+        let tmp_dir = assert_fs::TempDir::new().expect("can create tmp_dir");
+        std::fs::write(
+            tmp_dir.path().join("paper.aux"),
+            "\\citation{Higgs:2014aqa}\n\\@input{chap1.aux}\n",
+        )
+        .unwrap();
+        std::fs::write(
+            tmp_dir.path().join("chap1.aux"),
+            "\\citation{Guth:1980zm}\n\\citation{Linde:1981mu}\n\\bibdata{chap1}\n",
+        )
+        .unwrap();
+        // BibTeX does not tell keys apart by case
+        std::fs::write(
+            tmp_dir.path().join("chap1.bib"),
+            "@article{guth:1980ZM,\n}\n@article{HIGGS:2014AQA,\n}\n",
+        )
+        .unwrap();
+
+        let keys: Vec<String> = Inspirer::init(None)
+            .aux_file2missing(&tmp_dir.path().join("paper.aux"))
+            .unwrap()
+            .into_iter()
+            .map(|origin| origin.key)
+            .collect();
+
+        assert_eq!(keys, vec!["Linde:1981mu"]);
+    }
+
+    #[test]
+    fn test_aux_file_include_cycle() {
+        let tmp_dir = assert_fs::TempDir::new().expect("can create tmp_dir");
+        std::fs::write(
+            tmp_dir.path().join("a.aux"),
+            "\\citation{Higgs:2014aqa}\n\\@input{b.aux}\n",
+        )
+        .unwrap();
+        std::fs::write(
+            tmp_dir.path().join("b.aux"),
+            "\\citation{Guth:1980zm}\n\\@input{a.aux}\n\\@input{missing.aux}\n",
+        )
+        .unwrap();
+
+        let keys: Vec<String> = Inspirer::init(None)
+            .aux_file2key(&tmp_dir.path().join("a.aux"))
+            .unwrap()
+            .into_iter()
+            .map(|o| o.key)
+            .collect();
+
+        assert_eq!(keys, vec!["Guth:1980zm", "Higgs:2014aqa"]);
+    }
+
+    #[test]
+    fn test_aux_file_missing() {
+        let tmp_dir = assert_fs::TempDir::new().expect("can create tmp_dir");
+
+        assert!(Inspirer::init(None)
+            .aux_file2key(&tmp_dir.path().join("missing.aux"))
+            .is_err());
+    }
+
+    #[test]
+    fn test_tex_file_includes() {
+        let tmp_dir = assert_fs::TempDir::new().expect("can create tmp_dir");
+        std::fs::create_dir(tmp_dir.path().join("chapters")).unwrap();
+        std::fs::write(
+            tmp_dir.path().join("thesis.tex"),
+            "\\cite{Higgs:2014aqa}\n\\include{chapters/one}\n% \\input{old}\n\\input{thesis}\n",
+        )
+        .unwrap();
+        std::fs::write(
+            tmp_dir.path().join("chapters").join("one.tex"),
+            "\\citep{Guth:1980zm,Higgs:2014aqa}\n",
+        )
+        .unwrap();
+
+        let origins = Inspirer::init(None)
+            .tex_file2key(&tmp_dir.path().join("thesis.tex"))
+            .unwrap();
+
+        assert_eq!(
+            origins,
+            vec![
+                KeyOrigin {
+                    key: "Guth:1980zm".to_string(),
+                    file: tmp_dir.path().join("chapters").join("one.tex"),
+                },
+                KeyOrigin {
+                    key: "Higgs:2014aqa".to_string(),
+                    file: tmp_dir.path().join("thesis.tex"),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_log_example() {
+        assert_eq!(
+            Inspirer::init(None).log2key(include_str!("../example_files/test_bibtex.log").into()),
+            vec!["Higgs:2014aqa", "Higgs:2015mei"]
+        );
+    }
+
+    #[test]
+    fn test_log_variants() {
+        let input = "LaTeX Warning: Citation 'Guth:1980zm' on page 2 undefined on input line 12.\n\
+                     Package natbib Warning: Citation `Linde:1981mu' undefined on input line 3.\n\
+                     LaTeX Warning: Reference `fig:1' on page 2 undefined on input line 14.\n"
+            .to_string();
+
+        assert_eq!(
+            Inspirer::init(None).log2key(input),
+            vec!["Guth:1980zm", "Linde:1981mu"]
+        );
+    }
+
+    #[test]
+    fn test_log_wrapped() {
+        // Break lines after 79 characters, as TeX does
+        let wrap = |line: &str| -> String {
+            line.as_bytes()
+                .chunks(79)
+                .map(|c| std::str::from_utf8(c).unwrap())
+                .collect::<Vec<&str>>()
+                .join("\n")
+        };
+
+        let input = format!(
+            "{}\n\n{}\n",
+            wrap("LaTeX Warning: Citation `Abbott:2016blz-observation-of-gravitational-waves-from-a-binary' on page 1 undefined on input line 5."),
+            wrap("Package natbib Warning: Citation `1998PhRvD..58h4020O' on page 12 undefined on input line 143."),
+        );
+        assert_eq!(input.lines().count(), 5);
+
+        assert_eq!(
+            Inspirer::init(None).log2key(input),
+            vec![
+                "1998PhRvD..58h4020O",
+                "Abbott:2016blz-observation-of-gravitational-waves-from-a-binary"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_log_long_lines() {
+        // Lines of 79 characters which are not wrapped citation warnings
+        let key = "Abbott:2016blz-observation-of-gravitational-waves-bina";
+        let input = format!(
+            "Package biblatex Warning: The following entry could not be found\n\
+             (biblatex)                in the database:\n\
+             (biblatex)               {}\n\
+             (biblatex)                Please verify the spelling and rerun\n\
+             LaTeX Warning: Citation `Guth:1980zm' on page 123 undefined on input line 1234.\n\
+             LaTeX Warning: Citation `Linde:1981mu' undefined on input line 8.\n",
+            key
+        );
+        let lines: Vec<&str> = input.lines().collect();
+        assert_eq!(lines[2].len(), 79);
+        assert_eq!(lines[4].len(), 79);
+
+        assert_eq!(unwrap_log_lines(&input), input);
+        assert_eq!(
+            Inspirer::init(None).log2key(input),
+            vec![key, "Guth:1980zm", "Linde:1981mu"]
+        );
+    }
+
+    #[test]
+    fn test_log_biblatex() {
+        let input = r"
+Package biblatex Warning: The following entry could not be found
+(biblatex)                in the database:
+(biblatex)                Guth:1980zm
+(biblatex)                Please verify the spelling and rerun
+(biblatex)                LaTeX afterwards on input line 20.
+
+
+LaTeX Warning: Citation 'Linde:1981mu' on page 1 undefined on input line 8.
+"
+        .to_string();
+
+        assert_eq!(
+            Inspirer::init(None).log2key(input),
+            vec!["Guth:1980zm", "Linde:1981mu"]
+        );
+    }
+
+    #[test]
+    fn test_service() {
+        let inspirer = Inspirer::init(None);
+        let service = |key| inspirer.source(key).unwrap().service();
+
+        assert_eq!(service("Randall:1999ee"), "inspirehep.net");
+        assert_eq!(service("1999PhRvL..83.3370R"), "adsabs.harvard.edu");
+        assert_eq!(service("hep-th/9901001"), "arxiv.org");
+        assert_eq!(service("10.1103/PhysRevLett.116.061102"), "doi.org");
+    }
+
+    /// Claims every key, with the given confidence
+    struct Greedy(source::Confidence);
+
+    impl source::Source for Greedy {
+        fn name(&self) -> &'static str {
+            "greedy"
+        }
+
+        fn label(&self) -> &'static str {
+            "Greedy"
+        }
+
+        fn service(&self) -> &str {
+            "greedy.example.org"
+        }
+
+        fn recognises(&self, _key: &str) -> source::Confidence {
+            self.0
+        }
+
+        fn fetch(&self, key: &str) -> Result<String, InspirerError> {
+            Ok(format!("@misc{{{},\n}}\n", key))
+        }
+    }
+
+    #[test]
+    fn test_source() {
+        let inspirer = Inspirer::init(None).with_source(Greedy(source::Confidence::Maybe));
+
+        // Sources which are certain win over those which are not
+        assert_eq!(
+            inspirer.source("Randall:1999ee").unwrap().label(),
+            "INSPIRE"
+        );
+        assert_eq!(inspirer.source("not a key").unwrap().label(), "Greedy");
+        assert_eq!(inspirer.bibtex("not a key").unwrap().key(), "not a key");
+
+        // Of equally confident sources, the first wins
+        let inspirer = Inspirer::init(None).with_source(Greedy(source::Confidence::Certain));
+        assert_eq!(
+            inspirer.source("Randall:1999ee").unwrap().label(),
+            "INSPIRE"
+        );
+        assert_eq!(inspirer.source("not a key").unwrap().label(), "Greedy");
+
+        let labels: Vec<&str> = inspirer.sources().iter().map(|s| s.label()).collect();
+        assert_eq!(labels, vec!["INSPIRE", "ADS", "arXiv", "DOI", "Greedy"]);
+    }
+
+    /// Recognises keys starting with `LOST-`, but has no entries
+    struct Lost;
+
+    impl source::Source for Lost {
+        fn name(&self) -> &'static str {
+            "lost"
+        }
+
+        fn label(&self) -> &'static str {
+            "Lost"
+        }
+
+        fn service(&self) -> &str {
+            "lost.example.org"
+        }
+
+        fn recognises(&self, key: &str) -> source::Confidence {
+            if key.starts_with("LOST-") {
+                source::Confidence::Certain
+            } else {
+                source::Confidence::No
+            }
+        }
+
+        fn fetch(&self, key: &str) -> Result<String, InspirerError> {
+            Err(InspirerError::NotFound {
+                key: key.to_string(),
+                source: "Lost",
+            })
+        }
+    }
+
+    #[test]
+    fn test_local_source() {
+        let tmp_dir = assert_fs::TempDir::new().expect("can create tmp_dir");
+        let master = tmp_dir.path().join("master.bib");
+        std::fs::write(&master, "@article{Randall:1999ee,\n  note = {by hand}\n}\n").unwrap();
+        let cache = cache::Cache::new(tmp_dir.path().join("cache"));
+
+        let inspirer = Inspirer::init(None)
+            .with_cache(Some(cache.clone()))
+            .with_offline(true)
+            .with_local_source(source::Local::open(&[&master]).unwrap());
+
+        assert_eq!(inspirer.source("Randall:1999ee").unwrap().label(), "local");
+        assert_eq!(inspirer.source("Higgs:2014aqa").unwrap().label(), "INSPIRE");
+
+        let (entry, source) = inspirer.bibtex_with_source("Randall:1999ee").unwrap();
+        assert_eq!(source, "local");
+        assert_eq!(
+            entry.raw(),
+            Some("@article{Randall:1999ee,\n  note = {by hand}\n}")
+        );
+        assert_eq!(cache.get("local", "Randall:1999ee"), None);
+
+        // A stale cached entry does not hide the local one
+        cache
+            .put("inspire", "Randall:1999ee", "@article{Randall:1999ee,\n}\n")
+            .unwrap();
+        assert_eq!(
+            inspirer.bibtex_with_source("Randall:1999ee").unwrap().1,
+            "local"
+        );
+
+        match inspirer.bibtex("Higgs:2014aqa") {
+            Err(InspirerError::Offline { .. }) => {}
+            _ => panic!("only local entries are available offline"),
+        }
+    }
+
+    #[test]
+    fn test_policy() {
+        let inspirer = |policy| {
+            Inspirer::init(None)
+                .with_source(Lost)
+                .with_source(Greedy(source::Confidence::No))
+                .with_policy(policy)
+        };
+
+        // Only the source recognising the key is asked by default
+        match inspirer(policy::Policy::default()).bibtex("LOST-1") {
+            Err(InspirerError::NotFound { source, .. }) => assert_eq!(source, "Lost"),
+            _ => panic!("only Lost is asked"),
+        }
+
+        // The built-in sources cannot handle the key, so Greedy is the first to answer
+        let fallback = inspirer(policy::Policy::default().with_fallback(true));
+        let labels: Vec<&str> = fallback
+            .candidates("LOST-1")
+            .iter()
+            .map(|s| s.label())
+            .collect();
+        assert_eq!(
+            labels,
+            vec!["Lost", "INSPIRE", "ADS", "arXiv", "DOI", "Greedy"]
+        );
+        let (entry, source) = fallback.bibtex_with_source("LOST-1").unwrap();
+        assert_eq!(entry.key(), "LOST-1");
+        assert_eq!(source, "greedy");
+
+        // Failures to handle the key do not hide the failure of the source recognising it
+        let fallback = inspirer(
+            policy::Policy::default()
+                .with_fallback(true)
+                .with_disabled("greedy"),
+        );
+        match fallback.bibtex("LOST-1") {
+            Err(InspirerError::NotFound { source, .. }) => assert_eq!(source, "Lost"),
+            _ => panic!("the error of Lost is reported"),
+        }
+
+        let ordered =
+            inspirer(policy::Policy::default().with_order("lost=greedy".parse().unwrap()));
+        assert_eq!(ordered.candidates("LOST-1").len(), 1);
+        assert_eq!(ordered.bibtex("LOST-1").unwrap().key(), "LOST-1");
+
+        match inspirer(policy::Policy::default().with_disabled("lost")).bibtex("LOST-1") {
+            Err(InspirerError::UnknownKeyFormat { .. }) => {}
+            _ => panic!("no source is asked"),
+        }
+
+        // With fallback, disabling the sources set for the class leaves the others
+        let disabled = inspirer(
+            policy::Policy::default()
+                .with_fallback(true)
+                .with_order("lost=lost".parse().unwrap())
+                .with_disabled("lost"),
+        );
+        let labels: Vec<&str> = disabled
+            .candidates("LOST-1")
+            .iter()
+            .map(|s| s.label())
+            .collect();
+        assert_eq!(labels, vec!["INSPIRE", "ADS", "arXiv", "DOI", "Greedy"]);
+        assert_eq!(disabled.bibtex_with_source("LOST-1").unwrap().1, "greedy");
+    }
+
+    #[test]
+    fn test_bibtex_many_unknown_keys() {
+        let keys: Vec<String> = (0..10).map(|i| format!("not a key {}", i)).collect();
+        let keys: Vec<&str> = keys.iter().map(|k| k.as_str()).collect();
+
+        let results = Inspirer::init(None).with_concurrency(0).bibtex_many(&keys);
+
+        assert_eq!(results.len(), 10);
+        for (key, result) in keys.iter().zip(results) {
+            match result {
+                Err(InspirerError::UnknownKeyFormat { key: k }) => assert_eq!(k, *key),
+                r => panic!("unexpected result {:?}", r),
+            }
+        }
+        assert!(Inspirer::init(None).bibtex_many(&[]).is_empty());
+    }
+
+    /// Fetches keys starting with its name from a server
+    struct Remote {
+        name: &'static str,
+        url: reqwest::Url,
+    }
+
+    impl source::Source for Remote {
+        fn name(&self) -> &'static str {
+            self.name
+        }
+
+        fn label(&self) -> &'static str {
+            self.name
+        }
+
+        fn service(&self) -> &str {
+            self.name
+        }
+
+        fn recognises(&self, key: &str) -> source::Confidence {
+            if key.starts_with(self.name) {
+                source::Confidence::Certain
+            } else {
+                source::Confidence::No
+            }
+        }
+
+        fn fetch(&self, key: &str) -> Result<String, InspirerError> {
+            let logger = slog::Logger::root(slog::Discard, o!());
+            http::get(&logger, self.url.join(key).unwrap(), key, "Remote")
+        }
+    }
+
+    /// Answer requests for `/KEY` with an entry for KEY, slowly, keeping count of the most
+    /// requests being answered at once
+    fn serve_slowly() -> (reqwest::Url, Arc<AtomicUsize>) {
+        use std::io::{Read, Write};
+        use std::net::TcpListener;
+
+        let listener = TcpListener::bind("127.0.0.1:0").expect("can bind to a local port");
+        let url = reqwest::Url::parse(&format!(
+            "http://{}/",
+            listener.local_addr().expect("listener has an address")
+        ))
+        .unwrap();
+        let peak = Arc::new(AtomicUsize::new(0));
+        let active = Arc::new(AtomicUsize::new(0));
+
+        let server_peak = Arc::clone(&peak);
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.expect("client connects");
+                let (peak, active) = (Arc::clone(&server_peak), Arc::clone(&active));
+                thread::spawn(move || {
+                    let mut request = Vec::new();
+                    let mut buffer = [0; 1024];
+                    while !request.ends_with(b"\r\n\r\n") {
+                        match stream.read(&mut buffer) {
+                            Ok(0) | Err(_) => return,
+                            Ok(n) => request.extend_from_slice(&buffer[..n]),
+                        }
+                    }
+                    let request = String::from_utf8_lossy(&request).into_owned();
+                    let key = request.split(' ').nth(1).unwrap_or("/")[1..].to_string();
+
+                    peak.fetch_max(active.fetch_add(1, Ordering::SeqCst) + 1, Ordering::SeqCst);
+                    thread::sleep(Duration::from_millis(50));
+                    active.fetch_sub(1, Ordering::SeqCst);
+
+                    let body = format!("@misc{{{},\n}}\n", key);
+                    let _ = write!(
+                        stream,
+                        "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        body.len(),
+                        body
+                    );
+                });
+            }
+        });
+
+        (url, peak)
+    }
+
+    #[test]
+    fn test_bibtex_many_host_limit() {
+        let (url, peak) = serve_slowly();
+        // Two sources with their own services, which ask the same host
+        let inspirer = Inspirer::init(None)
+            .with_concurrency(2)
+            .with_source(Remote {
+                name: "one",
+                url: url.clone(),
+            })
+            .with_source(Remote { name: "two", url });
+
+        let keys: Vec<String> = (0..6)
+            .flat_map(|i| vec![format!("one-{}", i), format!("two-{}", i)])
+            .collect();
+        let keys: Vec<&str> = keys.iter().map(|k| k.as_str()).collect();
+
+        let results = inspirer.bibtex_many(&keys);
+
+        let fetched: Vec<&str> = results.iter().map(|r| r.as_ref().unwrap().key()).collect();
+        assert_eq!(fetched, keys);
+        assert_eq!(peak.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_with_inspire() {
+        let (url, _) = serve_slowly();
+        let client = inspire::Client::init(None).with_base_url(url.join("api/").unwrap());
+        let inspirer = Inspirer::init(None).with_inspire(client);
+
+        let labels: Vec<&str> = inspirer.sources().iter().map(|s| s.label()).collect();
+        assert_eq!(labels, vec!["INSPIRE", "ADS", "arXiv", "DOI"]);
+
+        // All the sources looking records up on INSPIRE ask the stub server
+        let entry = inspirer.bibtex("Randall:1999ee").unwrap();
+        assert!(entry.key().starts_with("api/literature?q=texkeys"));
+        for key in &["hep-th/9901001", "10.1103/PhysRevLett.116.061102"] {
+            let entry = inspirer.bibtex(key).unwrap();
+            assert_eq!(entry.key(), *key);
+        }
+    }
+
+    #[test]
+    fn test_with_inspire_disabled() {
+        let (url, peak) = serve_slowly();
+        let client = inspire::Client::init(None).with_base_url(url.join("api/").unwrap());
+
+        // Whichever is set first, arXiv and DOI keys are not sent to the stub server; whether
+        // arXiv and doi.org can be reached does not matter
+        let inspirers = vec![
+            Inspirer::init(None)
+                .with_inspire(client.clone())
+                .with_policy(policy::Policy::default().with_disabled("inspire")),
+            Inspirer::init(None)
+                .with_policy(policy::Policy::default().with_disabled("inspire"))
+                .with_inspire(client),
+        ];
+        for inspirer in &inspirers {
+            for key in &["hep-th/9901001", "10.1103/PhysRevLett.116.061102"] {
+                let _ = inspirer.bibtex(key);
+            }
+        }
+        assert_eq!(peak.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn test_put_output() {
+        let tmp_dir = assert_fs::TempDir::new().expect("can create tmp_dir");
+        let file_name = tmp_dir.path().join("refs.bib");
+        let file_name = file_name.to_str().unwrap();
+        std::fs::write(
+            file_name,
+            "% My references\n@article{Randall:1999ee,\n  title = {Old}\n}\n",
+        )
+        .unwrap();
+
+        let output = vec![
+            bib::Entry::parse("@article{Randall:1999ee,\n  title = {New}\n}\n").unwrap(),
+            bib::Entry::parse("@article{Randall:1999vf,\n}\n").unwrap(),
+        ];
+
+        let inspirer = Inspirer::init(None);
+        assert_eq!(
+            inspirer
+                .put_output(Some(file_name), output.clone())
+                .unwrap(),
+            vec!["Randall:1999ee"]
+        );
+        assert_eq!(
+            std::fs::read_to_string(file_name).unwrap(),
+            "% My references\n@article{Randall:1999ee,\n  title = {Old}\n}\n@article{Randall:1999vf,\n}\n"
+        );
+
+        let inspirer = inspirer.with_replace(true);
+        assert!(inspirer
+            .put_output(Some(file_name), output)
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            std::fs::read_to_string(file_name).unwrap(),
+            "% My references\n@article{Randall:1999ee,\n  title = {New}\n}\n@article{Randall:1999vf,\n}\n"
+        );
+
+        let inspirer = inspirer.with_format(Some(format::Format::default()));
+        let output =
+            vec![bib::Entry::parse("@ARTICLE{Randall:1999ee,\n TITLE = \"Newer\"\n}\n").unwrap()];
+        inspirer.put_output(Some(file_name), output).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(file_name).unwrap(),
+            "% My references\n@article{Randall:1999ee,\n  title = {Newer}\n}\n@article{Randall:1999vf,\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_put_output_newline() {
+        let tmp_dir = assert_fs::TempDir::new().expect("can create tmp_dir");
+        let file_name = tmp_dir.path().join("refs.bib");
+        let file_name = file_name.to_str().unwrap();
+        std::fs::write(file_name, "@article{Randall:1999ee,\n}").unwrap();
+
+        let output = vec![
+            bib::Entry::parse("@article{Randall:1999vf,\n}").unwrap(),
+            bib::Entry::parse("@article{Higgs:2014aqa,\n}\n").unwrap(),
+        ];
+        Inspirer::init(None)
+            .put_output(Some(file_name), output)
+            .unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(file_name).unwrap(),
+            "@article{Randall:1999ee,\n}\n@article{Randall:1999vf,\n}\n@article{Higgs:2014aqa,\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_offline() {
+        let tmp_dir = assert_fs::TempDir::new().expect("can create tmp_dir");
+        let cache = cache::Cache::new(tmp_dir.path());
+        cache
+            .put("inspire", "Randall:1999ee", "@article{Randall:1999ee,\n}\n")
+            .unwrap();
+
+        let inspirer = Inspirer::init(None)
+            .with_cache(Some(cache))
+            .with_offline(true);
+
+        let results = inspirer.bibtex_many(&["Randall:1999ee", "Randall:1999vf", "hep-th/9901001"]);
+
+        assert_eq!(
+            results[0].as_ref().unwrap().raw(),
+            Some("@article{Randall:1999ee,\n}")
+        );
+        match results[1] {
+            Err(InspirerError::Offline { ref key }) => assert_eq!(key, "Randall:1999vf"),
+            ref r => panic!("unexpected result {:?}", r),
+        }
+        match results[2] {
+            Err(InspirerError::Offline { ref key }) => assert_eq!(key, "hep-th/9901001"),
+            ref r => panic!("unexpected result {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_error_display() {
+        assert_eq!(
+            InspirerError::NotFound {
+                key: "Randall:1999ee".to_string(),
+                source: "INSPIRE",
+            }
+            .to_string(),
+            "INSPIRE has no entry for Randall:1999ee"
+        );
+        assert_eq!(
+            InspirerError::RateLimited {
+                url: "https://inspirehep.net/api/arxiv/1501.00001".to_string(),
+                retry_after: Some(Duration::from_secs(5)),
+            }
+            .to_string(),
+            "Rate limited by https://inspirehep.net/api/arxiv/1501.00001, retry after 5 s"
+        );
+        assert_eq!(
+            InspirerError::Network {
+                url: "http://export.arxiv.org/api/query".to_string(),
+                status: Some(503),
+                reason: "Service Unavailable".to_string(),
+            }
+            .to_string(),
+            "Request to http://export.arxiv.org/api/query failed with HTTP 503: Service Unavailable"
+        );
+        assert_eq!(
+            InspirerError::InvalidEntry {
+                key: "Randall:1999ee".to_string(),
+                reason: "expected `=` at byte 40".to_string(),
+            }
+            .to_string(),
+            "The entry for Randall:1999ee is not valid BibTeX: expected `=` at byte 40"
+        );
+        assert_eq!(
+            InspirerError::Config {
+                path: PathBuf::from("config.toml"),
+                reason: "unknown setting fallbak".to_string(),
+            }
+            .to_string(),
+            "Invalid configuration in config.toml: unknown setting fallbak"
+        );
+    }
+
     // TODO Similar tests on blg2key
 }
@@ -0,0 +1,235 @@
+//! Writing the output database without corrupting it
+//!
+//! The database is never written in place: the new contents go to a temporary file next to it,
+//! which is then renamed over the old one, so that an interrupted run leaves either the old or the
+//! new database.  Runs writing to the same database are serialised by a lock on a `.lock` file
+//! beside it, which is removed again when the lock is released.  The database itself cannot be
+//! locked, as the rename replaces it.
+//!
+//! A database which is a symbolic link, such as a link to a shared bibliography, is written
+//! through the link: the paths are those of the file it points to, see `resolve`.
+
+use fs2::FileExt;
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+
+/// The file written for `path`, following symbolic links
+///
+/// A path which does not exist yet is used as it is.
+pub(crate) fn resolve(path: &Path) -> io::Result<PathBuf> {
+    match fs::canonicalize(path) {
+        Ok(path) => Ok(path),
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(path.to_path_buf()),
+        Err(e) => Err(e),
+    }
+}
+
+/// An exclusive lock on a database, released when dropped
+pub(crate) struct Lock {
+    file: File,
+    path: PathBuf,
+}
+
+impl Drop for Lock {
+    fn drop(&mut self) {
+        // Removed while still locked, so that a process waiting for the lock notices that it has
+        // locked a file which is gone, see `lock`
+        let _ = fs::remove_file(&self.path);
+        let _ = self.file.unlock();
+    }
+}
+
+/// Take an exclusive lock on `path`, waiting for other processes to release it
+pub(crate) fn lock(logger: &slog::Logger, path: &Path) -> io::Result<Lock> {
+    let lock_path = with_suffix(path, ".lock");
+    loop {
+        let lock_file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&lock_path)?;
+
+        if lock_file.try_lock_exclusive().is_err() {
+            info!(logger, "Waiting for another process to finish writing";
+                  "file_name" => lock_path.display().to_string());
+            lock_file.lock_exclusive()?;
+        }
+
+        // The process which held the lock may have removed the file, and another process may
+        // have created a new one, so the lock only counts if the file is still there
+        if is_same_file(&lock_file, &lock_path)? {
+            return Ok(Lock {
+                file: lock_file,
+                path: lock_path,
+            });
+        }
+    }
+}
+
+#[cfg(unix)]
+fn is_same_file(file: &File, path: &Path) -> io::Result<bool> {
+    use std::os::unix::fs::MetadataExt;
+
+    let metadata = file.metadata()?;
+    match fs::metadata(path) {
+        Ok(other) => Ok(metadata.dev() == other.dev() && metadata.ino() == other.ino()),
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// Files which are open cannot be removed on other platforms
+#[cfg(not(unix))]
+fn is_same_file(_file: &File, path: &Path) -> io::Result<bool> {
+    Ok(path.exists())
+}
+
+/// Atomically replace the contents of `path`, optionally keeping the old file as `<path>.bak`
+///
+/// The permissions of an existing file are kept.
+pub(crate) fn replace(path: &Path, contents: &str, backup: bool) -> io::Result<()> {
+    let permissions = match fs::metadata(path) {
+        Ok(metadata) => Some(metadata.permissions()),
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e),
+    };
+
+    let tmp_path = with_suffix(path, &format!(".{}.tmp", process::id()));
+    let result = write_synced(&tmp_path, contents)
+        .and_then(|_| match permissions {
+            Some(ref permissions) => fs::set_permissions(&tmp_path, permissions.clone()),
+            None => Ok(()),
+        })
+        .and_then(|_| {
+            if backup && permissions.is_some() {
+                fs::copy(path, with_suffix(path, ".bak"))?;
+            }
+            fs::rename(&tmp_path, path)
+        });
+
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+
+    result
+}
+
+/// Write a file and wait until it is on disk, so that it can be renamed safely
+fn write_synced(path: &Path, contents: &str) -> io::Result<()> {
+    let mut file = File::create(path)?;
+    file.write_all(contents.as_bytes())?;
+    file.sync_all()
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(suffix);
+
+    PathBuf::from(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_replace() {
+        let tmp_dir = assert_fs::TempDir::new().expect("can create tmp_dir");
+        let path = tmp_dir.path().join("refs.bib");
+
+        replace(&path, "new\n", true).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new\n");
+        assert!(!tmp_dir.path().join("refs.bib.bak").exists());
+
+        replace(&path, "newer\n", false).unwrap();
+        assert!(!tmp_dir.path().join("refs.bib.bak").exists());
+
+        replace(&path, "newest\n", true).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "newest\n");
+        assert_eq!(
+            fs::read_to_string(tmp_dir.path().join("refs.bib.bak")).unwrap(),
+            "newer\n"
+        );
+
+        // Only the database, its backup and nothing temporary
+        assert_eq!(fs::read_dir(tmp_dir.path()).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn test_lock() {
+        let tmp_dir = assert_fs::TempDir::new().expect("can create tmp_dir");
+        let path = tmp_dir.path().join("refs.bib");
+        let logger = slog::Logger::root(slog::Discard, o!());
+
+        let lock_file = lock(&logger, &path).unwrap();
+
+        let other = File::open(tmp_dir.path().join("refs.bib.lock")).unwrap();
+        assert!(other.try_lock_exclusive().is_err());
+
+        drop(lock_file);
+        assert!(other.try_lock_exclusive().is_ok());
+        other.unlock().unwrap();
+
+        // No lock file is left behind
+        assert_eq!(fs::read_dir(tmp_dir.path()).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_lock_waiting() {
+        let tmp_dir = assert_fs::TempDir::new().expect("can create tmp_dir");
+        let path = tmp_dir.path().join("refs.bib");
+        let logger = slog::Logger::root(slog::Discard, o!());
+
+        let first = lock(&logger, &path).unwrap();
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let waiting = {
+            let (logger, path) = (logger.clone(), path.clone());
+            std::thread::spawn(move || {
+                let second = lock(&logger, &path).unwrap();
+                sender.send(()).unwrap();
+                second
+            })
+        };
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        assert!(receiver.try_recv().is_err());
+
+        drop(first);
+        let second = waiting.join().unwrap();
+        receiver.recv().unwrap();
+
+        // The waiting process holds a lock on the lock file which is there now
+        let other = File::open(tmp_dir.path().join("refs.bib.lock")).unwrap();
+        assert!(other.try_lock_exclusive().is_err());
+        drop(second);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_resolve_symlink() {
+        let tmp_dir = assert_fs::TempDir::new().expect("can create tmp_dir");
+        let master = tmp_dir.path().join("master.bib");
+        fs::write(&master, "old\n").unwrap();
+        let link = tmp_dir.path().join("refs.bib");
+        std::os::unix::fs::symlink(&master, &link).unwrap();
+
+        let path = resolve(&link).unwrap();
+        replace(&path, "new\n", true).unwrap();
+
+        assert!(fs::symlink_metadata(&link)
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        assert_eq!(fs::read_to_string(&link).unwrap(), "new\n");
+        assert_eq!(fs::read_to_string(&master).unwrap(), "new\n");
+        assert_eq!(
+            fs::read_to_string(tmp_dir.path().join("master.bib.bak")).unwrap(),
+            "old\n"
+        );
+
+        let missing = tmp_dir.path().join("missing.bib");
+        assert_eq!(resolve(&missing).unwrap(), missing);
+    }
+}
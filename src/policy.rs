@@ -0,0 +1,315 @@
+//! Which sources are asked for which keys
+//!
+//! By default a key is fetched only from the source which recognises it, see
+//! `Inspirer::source`.  Keys are grouped into classes by the name of that source, so that the
+//! class of `1999PhRvL..83.3370R` is `ads`.  A [`Policy`](struct.Policy.html) can change, for
+//! each class, which sources are asked and in what order, fall back to the other sources when a
+//! fetch fails, and disable sources altogether.
+//!
+//! A policy can be read from a TOML file:
+//!
+//! ```toml
+//! # Ask the other sources when the one recognising a key fails
+//! fallback = true
+//! # Never ask these sources
+//! disabled = ["doi"]
+//!
+//! # The sources to ask for keys of each class, in order
+//! [order]
+//! ads = ["inspire", "ads"]
+//! ```
+
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::InspirerError;
+
+/// The sources to ask for keys of a class, in order
+///
+/// Parsed from `CLASS=SOURCE,SOURCE,...`, such as `ads=inspire,ads`.
+///
+/// # Examples
+///
+/// ```
+/// use inspirer::policy::Order;
+///
+/// let order: Order = "ads=inspire,ads".parse().unwrap();
+///
+/// assert_eq!(order.class, "ads");
+/// assert_eq!(order.sources, vec!["inspire", "ads"]);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Order {
+    pub class: String,
+    pub sources: Vec<String>,
+}
+
+impl FromStr for Order {
+    type Err = PolicyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(2, '=');
+        let class = parts.next().unwrap_or("").trim();
+        let sources: Vec<String> = parts
+            .next()
+            .unwrap_or("")
+            .split(',')
+            .map(|name| name.trim().to_lowercase())
+            .filter(|name| !name.is_empty())
+            .collect();
+
+        if class.is_empty() || sources.is_empty() {
+            Err(PolicyError(format!(
+                "invalid order {:?}, expected CLASS=SOURCE,SOURCE,...",
+                s
+            )))
+        } else {
+            Ok(Order {
+                class: class.to_lowercase(),
+                sources,
+            })
+        }
+    }
+}
+
+/// A policy could not be parsed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PolicyError(String);
+
+impl fmt::Display for PolicyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for PolicyError {}
+
+/// Which sources are asked for which keys
+///
+/// # Examples
+///
+/// ```
+/// use inspirer::policy::Policy;
+///
+/// let policy = Policy::default()
+///     .with_order("ads=ads,inspire".parse().unwrap())
+///     .with_disabled("doi");
+///
+/// assert_eq!(
+///     policy.order("ads"),
+///     Some(&["ads".to_string(), "inspire".to_string()][..])
+/// );
+/// assert_eq!(policy.order("inspire"), None);
+/// assert!(!policy.is_enabled("doi"));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Policy {
+    orders: Vec<Order>,
+    disabled: Vec<String>,
+    fallback: bool,
+}
+
+impl Policy {
+    /// Set the sources to ask for keys of a class, replacing any earlier order for the class
+    ///
+    /// Only the sources listed are asked, unless `with_fallback` is set, in which case the other
+    /// sources are asked after them.
+    pub fn with_order(mut self, order: Order) -> Self {
+        self.orders.retain(|o| o.class != order.class);
+        self.orders.push(order);
+        self
+    }
+
+    /// Never ask a source
+    ///
+    /// Without `with_fallback`, keys only the source is asked for are treated as keys of unknown
+    /// format.
+    pub fn with_disabled(mut self, source: &str) -> Self {
+        let source = source.to_lowercase();
+        if !self.disabled.contains(&source) {
+            self.disabled.push(source);
+        }
+        self
+    }
+
+    /// Set whether the other sources are asked when the source recognising a key fails
+    ///
+    /// The other sources are asked in the order they were added to the `Inspirer`, after the
+    /// sources set for the class by `with_order`.
+    pub fn with_fallback(mut self, fallback: bool) -> Self {
+        self.fallback = fallback;
+        self
+    }
+
+    /// The sources to ask for keys of a class, if set
+    pub fn order(&self, class: &str) -> Option<&[String]> {
+        self.orders
+            .iter()
+            .find(|o| o.class == class)
+            .map(|o| o.sources.as_slice())
+    }
+
+    pub fn fallback(&self) -> bool {
+        self.fallback
+    }
+
+    pub fn is_enabled(&self, source: &str) -> bool {
+        !self.disabled.iter().any(|s| s == source)
+    }
+
+    /// Names of all the sources and classes the policy mentions
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = Vec::new();
+        for order in &self.orders {
+            names.push(&order.class);
+            names.extend(order.sources.iter().map(|s| s.as_str()));
+        }
+        names.extend(self.disabled.iter().map(|s| s.as_str()));
+        names.sort_unstable();
+        names.dedup();
+
+        names
+    }
+
+    /// Parse a policy from TOML, as described in the module documentation
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use inspirer::policy::Policy;
+    ///
+    /// let policy = Policy::from_toml("fallback = true\n[order]\nads = [\"inspire\"]\n").unwrap();
+    ///
+    /// assert!(policy.fallback());
+    /// assert_eq!(policy.order("ads"), Some(&["inspire".to_string()][..]));
+    /// assert!(Policy::from_toml("fallback = \"yes\"").is_err());
+    /// ```
+    pub fn from_toml(text: &str) -> Result<Self, PolicyError> {
+        let value: toml::Value = text
+            .parse()
+            .map_err(|e: toml::de::Error| PolicyError(e.to_string()))?;
+        let table = value
+            .as_table()
+            .ok_or_else(|| PolicyError("expected a table".to_string()))?;
+
+        let mut policy = Policy::default();
+        for (name, value) in table {
+            match name.as_str() {
+                "fallback" => {
+                    policy.fallback = value
+                        .as_bool()
+                        .ok_or_else(|| PolicyError("fallback must be true or false".to_string()))?;
+                }
+                "disabled" => {
+                    for source in string_array(value, "disabled")? {
+                        policy = policy.with_disabled(source);
+                    }
+                }
+                "order" => {
+                    let orders = value
+                        .as_table()
+                        .ok_or_else(|| PolicyError("order must be a table".to_string()))?;
+                    for (class, sources) in orders {
+                        policy = policy.with_order(Order {
+                            class: class.to_lowercase(),
+                            sources: string_array(sources, class)?
+                                .into_iter()
+                                .map(|s| s.to_lowercase())
+                                .collect(),
+                        });
+                    }
+                }
+                _ => return Err(PolicyError(format!("unknown setting {}", name))),
+            }
+        }
+
+        Ok(policy)
+    }
+
+    /// Read a policy from a TOML file
+    pub fn load(path: &Path) -> Result<Self, InspirerError> {
+        let text = fs::read_to_string(path).map_err(InspirerError::Io)?;
+
+        Policy::from_toml(&text).map_err(|e| InspirerError::Config {
+            path: path.to_path_buf(),
+            reason: e.to_string(),
+        })
+    }
+
+    /// The user's configuration file for inspirer
+    ///
+    /// This is `$XDG_CONFIG_HOME/inspirer/config.toml` (by default
+    /// `~/.config/inspirer/config.toml`) on Linux, and the platform's equivalent elsewhere.
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("inspirer").join("config.toml"))
+    }
+}
+
+fn string_array<'a>(value: &'a toml::Value, name: &str) -> Result<Vec<&'a str>, PolicyError> {
+    value
+        .as_array()
+        .and_then(|values| values.iter().map(|v| v.as_str()).collect())
+        .ok_or_else(|| PolicyError(format!("{} must be a list of source names", name)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_toml() {
+        let policy = Policy::from_toml(
+            r#"
+fallback = true
+disabled = ["DOI", "doi"]
+
+[order]
+ads = ["inspire", "ads"]
+arxiv = ["arxiv"]
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            policy,
+            Policy::default()
+                .with_fallback(true)
+                .with_disabled("doi")
+                .with_order("ads=inspire,ads".parse().unwrap())
+                .with_order("arxiv=arxiv".parse().unwrap())
+        );
+        assert_eq!(policy.names(), vec!["ads", "arxiv", "doi", "inspire"]);
+    }
+
+    #[test]
+    fn test_from_toml_errors() {
+        assert!(Policy::from_toml("fallback = 1").is_err());
+        assert!(Policy::from_toml("disabled = \"doi\"").is_err());
+        assert!(Policy::from_toml("[order]\nads = [1]").is_err());
+        assert!(Policy::from_toml("fallbak = true").is_err());
+        assert!(Policy::from_toml("[order").is_err());
+        assert_eq!(Policy::from_toml(""), Ok(Policy::default()));
+    }
+
+    #[test]
+    fn test_order() {
+        assert_eq!(
+            "ADS = inspire, ads,".parse(),
+            Ok(Order {
+                class: "ads".to_string(),
+                sources: vec!["inspire".to_string(), "ads".to_string()],
+            })
+        );
+        assert!("ads".parse::<Order>().is_err());
+        assert!("ads=".parse::<Order>().is_err());
+        assert!("=ads".parse::<Order>().is_err());
+
+        // A later order for the same class replaces the earlier one
+        let policy = Policy::default()
+            .with_order("ads=ads".parse().unwrap())
+            .with_order("ads=inspire".parse().unwrap());
+        assert_eq!(policy.order("ads"), Some(&["inspire".to_string()][..]));
+    }
+}
@@ -0,0 +1,361 @@
+//! Summaries of which keys could be resolved
+//!
+//! A [`Report`](struct.Report.html) is printed at the end of a run so that failed keys do not go
+//! unnoticed.  It can be written as text for people or as JSON for build scripts.
+
+use std::fmt;
+
+use crate::{Inspirer, InspirerError};
+
+/// Exit status when every key was resolved
+pub const EXIT_SUCCESS: i32 = 0;
+/// Exit status when the input could not be read or the output could not be written
+pub const EXIT_IO_ERROR: i32 = 1;
+/// Exit status when some keys were not resolved
+pub const EXIT_UNRESOLVED: i32 = 2;
+/// Exit status when a server could not be reached
+pub const EXIT_NETWORK_UNAVAILABLE: i32 = 3;
+
+/// A key whose entry was found
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Resolved {
+    pub key: String,
+    /// Name of the source, such as "INSPIRE"
+    pub source: &'static str,
+}
+
+/// A key whose entry could not be fetched
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Failure {
+    pub key: String,
+    pub reason: String,
+    /// Whether the failure was because a server could not be reached
+    pub network_unavailable: bool,
+}
+
+/// The outcome of fetching a list of keys
+///
+/// # Examples
+///
+/// ```
+/// use inspirer::report::Report;
+///
+/// let inspirer = inspirer::Inspirer::init(None);
+///
+/// let keys = ["not a key"];
+/// let results = inspirer.bibtex_many_with_source(&keys);
+/// let report = Report::new(&inspirer, &keys, &results);
+///
+/// assert_eq!(report.unrecognised, vec!["not a key"]);
+/// assert!(!report.is_complete());
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Report {
+    /// Keys whose entries were found, in the order they were given
+    pub resolved: Vec<Resolved>,
+    /// Keys which do not look like keys of any known source
+    pub unrecognised: Vec<String>,
+    /// Keys which look right, but whose entries could not be fetched
+    pub failed: Vec<Failure>,
+    /// Resolved keys which were not written because the output already has entries for them
+    pub skipped: Vec<String>,
+    /// Names of the sources, in the order they are reported
+    pub sources: Vec<&'static str>,
+}
+
+impl Report {
+    /// Summarise the results of `inspirer.bibtex_many_with_source` for `keys`
+    ///
+    /// Resolved keys are credited to the source named in their result, the source which answered.
+    pub fn new<T>(
+        inspirer: &Inspirer,
+        keys: &[&str],
+        results: &[Result<(T, &'static str), InspirerError>],
+    ) -> Self {
+        let mut report = Report {
+            sources: inspirer.sources().iter().map(|s| s.label()).collect(),
+            ..Report::default()
+        };
+
+        for (key, result) in keys.iter().zip(results) {
+            match *result {
+                Ok((_, name)) => report.resolved.push(Resolved {
+                    key: key.to_string(),
+                    source: inspirer
+                        .sources()
+                        .iter()
+                        .find(|s| s.name() == name)
+                        .map_or(name, |s| s.label()),
+                }),
+                Err(InspirerError::UnknownKeyFormat { .. }) => {
+                    report.unrecognised.push(key.to_string())
+                }
+                Err(ref e) => report.failed.push(Failure {
+                    key: key.to_string(),
+                    reason: e.to_string(),
+                    network_unavailable: matches!(*e, InspirerError::Network { status: None, .. }),
+                }),
+            }
+        }
+
+        report
+    }
+
+    /// Whether every key was resolved
+    pub fn is_complete(&self) -> bool {
+        self.unrecognised.is_empty() && self.failed.is_empty()
+    }
+
+    /// The exit status for a run with this outcome
+    ///
+    /// Keys which were not recognised are usually entries written by hand, which are expected to
+    /// be in the user's own database, so only count as unresolved if `strict` is set.
+    ///
+    /// ```
+    /// use inspirer::report::{self, Report};
+    ///
+    /// let report = Report {
+    ///     unrecognised: vec!["Einstein1905".to_string()],
+    ///     ..Report::default()
+    /// };
+    ///
+    /// assert_eq!(report.exit_code(false), report::EXIT_SUCCESS);
+    /// assert_eq!(report.exit_code(true), report::EXIT_UNRESOLVED);
+    /// ```
+    pub fn exit_code(&self, strict: bool) -> i32 {
+        if self.failed.iter().any(|f| f.network_unavailable) {
+            EXIT_NETWORK_UNAVAILABLE
+        } else if !self.failed.is_empty() || (strict && !self.unrecognised.is_empty()) {
+            EXIT_UNRESOLVED
+        } else {
+            EXIT_SUCCESS
+        }
+    }
+
+    /// The report as a JSON object
+    ///
+    /// ```json
+    /// {
+    ///   "resolved": {"INSPIRE": ["Higgs:2014aqa"], "ADS": [], "arXiv": [], "DOI": []},
+    ///   "unrecognised": ["not a key"],
+    ///   "failed": [{"key": "Guth:1980zm", "reason": "INSPIRE has no entry for Guth:1980zm"}],
+    ///   "skipped": []
+    /// }
+    /// ```
+    pub fn to_json(&self) -> String {
+        let mut resolved = serde_json::Map::new();
+        for source in &self.sources {
+            resolved.insert(source.to_string(), self.keys_from(source).into());
+        }
+
+        let failed: Vec<serde_json::Value> = self
+            .failed
+            .iter()
+            .map(|f| json!({"key": f.key, "reason": f.reason}))
+            .collect();
+
+        json!({
+            "resolved": resolved,
+            "unrecognised": self.unrecognised,
+            "failed": failed,
+            "skipped": self.skipped,
+        })
+        .to_string()
+    }
+
+    fn keys_from(&self, source: &str) -> Vec<&str> {
+        self.resolved
+            .iter()
+            .filter(|r| r.source == source)
+            .map(|r| r.key.as_str())
+            .collect()
+    }
+}
+
+/// The report as text, one line per group of keys and per failure
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let total = self.resolved.len() + self.unrecognised.len() + self.failed.len();
+        writeln!(f, "Resolved {} of {} keys", self.resolved.len(), total)?;
+
+        for source in &self.sources {
+            let keys = self.keys_from(source);
+            if !keys.is_empty() {
+                writeln!(f, "  from {}: {}", source, keys.join(", "))?;
+            }
+        }
+        if !self.skipped.is_empty() {
+            writeln!(
+                f,
+                "Skipped keys already in the output: {}",
+                self.skipped.join(", ")
+            )?;
+        }
+        if !self.unrecognised.is_empty() {
+            writeln!(f, "Unrecognised keys: {}", self.unrecognised.join(", "))?;
+        }
+        if !self.failed.is_empty() {
+            writeln!(f, "Failed keys:")?;
+            for failure in &self.failed {
+                writeln!(f, "  {}: {}", failure.key, failure.reason)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report() -> Report {
+        let keys = [
+            "Higgs:2014aqa",
+            "not a key",
+            "1982PhRvL..48.1220A",
+            "Guth:1980zm",
+            "Higgs:2015mei",
+        ];
+        let results = vec![
+            Ok((String::new(), "inspire")),
+            Err(InspirerError::UnknownKeyFormat {
+                key: "not a key".to_string(),
+            }),
+            Ok((String::new(), "ads")),
+            Err(InspirerError::NotFound {
+                key: "Guth:1980zm".to_string(),
+                source: "INSPIRE",
+            }),
+            Ok((String::new(), "inspire")),
+        ];
+
+        let mut report = Report::new(&Inspirer::init(None), &keys, &results);
+        report.skipped = vec!["Higgs:2015mei".to_string()];
+
+        report
+    }
+
+    #[test]
+    fn test_new() {
+        let report = report();
+
+        assert_eq!(
+            report.resolved,
+            vec![
+                Resolved {
+                    key: "Higgs:2014aqa".to_string(),
+                    source: "INSPIRE"
+                },
+                Resolved {
+                    key: "1982PhRvL..48.1220A".to_string(),
+                    source: "ADS"
+                },
+                Resolved {
+                    key: "Higgs:2015mei".to_string(),
+                    source: "INSPIRE"
+                },
+            ]
+        );
+        assert_eq!(report.unrecognised, vec!["not a key"]);
+        assert_eq!(
+            report.failed,
+            vec![Failure {
+                key: "Guth:1980zm".to_string(),
+                reason: "INSPIRE has no entry for Guth:1980zm".to_string(),
+                network_unavailable: false,
+            }]
+        );
+        assert!(!report.is_complete());
+        assert!(Report::default().is_complete());
+    }
+
+    #[test]
+    fn test_new_credits_answering_source() {
+        // With a fallback policy, INSPIRE can answer for an ADS bibcode
+        let results = vec![Ok(((), "inspire"))];
+        let report = Report::new(&Inspirer::init(None), &["1982PhRvL..48.1220A"], &results);
+
+        assert_eq!(
+            report.resolved,
+            vec![Resolved {
+                key: "1982PhRvL..48.1220A".to_string(),
+                source: "INSPIRE"
+            }]
+        );
+    }
+
+    #[test]
+    fn test_exit_code() {
+        assert_eq!(Report::default().exit_code(true), EXIT_SUCCESS);
+        assert_eq!(report().exit_code(false), EXIT_UNRESOLVED);
+
+        let keys = ["Higgs:2014aqa", "Guth:1980zm"];
+        let results: Vec<Result<(String, &str), InspirerError>> = vec![
+            Err(InspirerError::NotFound {
+                key: "Higgs:2014aqa".to_string(),
+                source: "INSPIRE",
+            }),
+            Err(InspirerError::Network {
+                url: "https://inspirehep.net/".to_string(),
+                status: None,
+                reason: "failed to lookup address".to_string(),
+            }),
+        ];
+        assert_eq!(
+            Report::new(&Inspirer::init(None), &keys, &results).exit_code(false),
+            EXIT_NETWORK_UNAVAILABLE
+        );
+
+        // A server which answers with an error is reachable
+        let results = vec![
+            Ok((String::new(), "inspire")),
+            Err(InspirerError::Network {
+                url: "https://inspirehep.net/".to_string(),
+                status: Some(503),
+                reason: "Service Unavailable".to_string(),
+            }),
+        ];
+        assert_eq!(
+            Report::new(&Inspirer::init(None), &keys, &results).exit_code(false),
+            EXIT_UNRESOLVED
+        );
+    }
+
+    #[test]
+    fn test_text() {
+        assert_eq!(
+            report().to_string(),
+            "Resolved 3 of 5 keys
+  from INSPIRE: Higgs:2014aqa, Higgs:2015mei
+  from ADS: 1982PhRvL..48.1220A
+Skipped keys already in the output: Higgs:2015mei
+Unrecognised keys: not a key
+Failed keys:
+  Guth:1980zm: INSPIRE has no entry for Guth:1980zm
+"
+        );
+    }
+
+    #[test]
+    fn test_json() {
+        let json: serde_json::Value = serde_json::from_str(&report().to_json()).unwrap();
+
+        assert_eq!(
+            json,
+            json!({
+                "resolved": {
+                    "INSPIRE": ["Higgs:2014aqa", "Higgs:2015mei"],
+                    "ADS": ["1982PhRvL..48.1220A"],
+                    "arXiv": [],
+                    "DOI": [],
+                },
+                "unrecognised": ["not a key"],
+                "failed": [
+                    {"key": "Guth:1980zm", "reason": "INSPIRE has no entry for Guth:1980zm"},
+                ],
+                "skipped": ["Higgs:2015mei"],
+            })
+        );
+    }
+}
@@ -0,0 +1,752 @@
+//! Services BibTeX entries are fetched from
+//!
+//! Each service is a [`Source`](trait.Source.html), which recognises the keys it can resolve and
+//! fetches their entries.  An [`Inspirer`](../struct.Inspirer.html) asks each of its sources
+//! whether it recognises a key, and fetches the entry from the one that is most confident.  The
+//! built-in sources for INSPIRE, ADS, arXiv and DOIs are in this module; others can be added with
+//! `Inspirer::with_source`.
+//!
+//! # Examples
+//!
+//! A source for an institute's own library, whose keys look like `LIB-1234`:
+//!
+//! ```
+//! use inspirer::source::{Confidence, Source};
+//! use inspirer::InspirerError;
+//!
+//! struct Library;
+//!
+//! impl Source for Library {
+//!     fn name(&self) -> &'static str {
+//!         "library"
+//!     }
+//!
+//!     fn label(&self) -> &'static str {
+//!         "Library"
+//!     }
+//!
+//!     fn service(&self) -> &str {
+//!         "library.example.org"
+//!     }
+//!
+//!     fn recognises(&self, key: &str) -> Confidence {
+//!         if key.starts_with("LIB-") {
+//!             Confidence::Certain
+//!         } else {
+//!             Confidence::No
+//!         }
+//!     }
+//!
+//!     fn fetch(&self, key: &str) -> Result<String, InspirerError> {
+//!         Ok(format!("@book{{{},\n  title = {{Held by the library}}\n}}\n", key))
+//!     }
+//! }
+//!
+//! let inspirer = inspirer::Inspirer::init(None).with_source(Library);
+//!
+//! assert_eq!(inspirer.source("LIB-1234").unwrap().label(), "Library");
+//! assert_eq!(inspirer.bibtex("LIB-1234").unwrap().key(), "LIB-1234");
+//! ```
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use reqwest::Url;
+use slog::DrainExt;
+
+use crate::arxiv::{self, replace_key};
+use crate::bib;
+use crate::doi;
+use crate::http;
+use crate::inspire;
+use crate::InspirerError;
+
+/// How sure a source is that a key is one of its own
+///
+/// Confidences are ordered, `No` being the least.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Confidence {
+    /// The key is not one of the source's
+    No,
+    /// The key could be one of the source's, but could also be another's
+    Maybe,
+    /// The key is in the format the source uses
+    Certain,
+}
+
+/// A service BibTeX entries are fetched from
+///
+/// Sources are shared between the threads of `Inspirer::bibtex_many`, so must be `Send` and
+/// `Sync`.
+pub trait Source: Send + Sync {
+    /// Short name of the source, used to keep cached entries apart, such as "inspire"
+    ///
+    /// The name ends up in file names, so should be lowercase letters only.
+    fn name(&self) -> &'static str;
+
+    /// Name of the source as shown to users, such as "INSPIRE"
+    fn label(&self) -> &'static str;
+
+    /// The service queried for entries of this source, such as "inspirehep.net"
+    ///
+    /// `Inspirer::bibtex_many` shares keys between its workers by service, so that requests to
+    /// each service are limited even if the source does not send them through this crate.
+    fn service(&self) -> &str;
+
+    /// How sure the source is that it can resolve `key`
+    fn recognises(&self, key: &str) -> Confidence;
+
+    /// Fetch the BibTeX entry for `key`, with `key` as its key
+    fn fetch(&self, key: &str) -> Result<String, InspirerError>;
+
+    /// Whether entries abbreviate journal names with AASTeX macros, see `journals`
+    fn journal_macros(&self) -> bool {
+        false
+    }
+
+    /// Whether entries are read from local files rather than fetched from a server
+    ///
+    /// Local sources are quick to ask, so their entries are not cached, and they are asked even
+    /// when working offline.
+    fn local(&self) -> bool {
+        false
+    }
+}
+
+fn certain_if(recognised: bool) -> Confidence {
+    if recognised {
+        Confidence::Certain
+    } else {
+        Confidence::No
+    }
+}
+
+/// INSPIRE, for keys such as `Randall:1999ee`
+///
+/// INSPIRE also knows the ADS bibcodes of many papers, so can be asked for bibcodes as a
+/// fallback, see `policy`.
+pub struct Inspire {
+    client: inspire::Client,
+}
+
+impl Default for Inspire {
+    fn default() -> Self {
+        Inspire::with_client(inspire::Client::init(None))
+    }
+}
+
+impl Inspire {
+    /// Use a client of another instance of the INSPIRE API, see `inspire::Client::with_base_url`
+    pub fn with_client(client: inspire::Client) -> Self {
+        Inspire { client }
+    }
+
+    /// Search INSPIRE for the paper with an ADS bibcode
+    fn fetch_by_bibcode(&self, bibcode: &str) -> Result<String, InspirerError> {
+        let query = format!("external_system_identifiers.value:\"{}\"", bibcode);
+        let bibtex = self
+            .client
+            .search(&query, inspire::Serialisation::Bibtex)?
+            .ok_or(InspirerError::NotFound {
+                key: bibcode.to_string(),
+                source: "INSPIRE",
+            })?;
+
+        replace_key(&bibtex, bibcode).ok_or_else(|| InspirerError::MalformedResponse {
+            url: self
+                .client
+                .search_url(&query, inspire::Serialisation::Bibtex)
+                .to_string(),
+            reason: "response is not a BibTeX entry".to_string(),
+        })
+    }
+}
+
+impl Source for Inspire {
+    fn name(&self) -> &'static str {
+        "inspire"
+    }
+
+    fn label(&self) -> &'static str {
+        "INSPIRE"
+    }
+
+    fn service(&self) -> &str {
+        "inspirehep.net"
+    }
+
+    fn recognises(&self, key: &str) -> Confidence {
+        certain_if(inspire::validate_texkey(key))
+    }
+
+    fn fetch(&self, key: &str) -> Result<String, InspirerError> {
+        if libads::validate_bib_code(key) {
+            return self.fetch_by_bibcode(key);
+        }
+        if !inspire::validate_texkey(key) {
+            return Err(InspirerError::UnknownKeyFormat {
+                key: key.to_string(),
+            });
+        }
+
+        self.client.bibtex(inspire::Identifier::Texkey(key))
+    }
+}
+
+/// ADS's BibTeX export, which answers with a header followed by the entry
+const ADS_BASE_URL: &str = "http://adsabs.harvard.edu/cgi-bin/nph-bib_query/";
+
+/// ADS, for bibcodes such as `1999PhRvL..83.3370R`
+pub struct Ads {
+    logger: slog::Logger,
+    base_url: Url,
+}
+
+impl Default for Ads {
+    fn default() -> Self {
+        Ads::with_base_url(Url::parse(ADS_BASE_URL).expect("Static and parseable"))
+    }
+}
+
+impl Ads {
+    /// Send requests to another instance of ADS's BibTeX export
+    pub fn with_base_url(base_url: Url) -> Self {
+        Ads {
+            logger: slog::Logger::root(slog_stdlog::StdLog.fuse(), o!()),
+            base_url,
+        }
+    }
+}
+
+impl Source for Ads {
+    fn name(&self) -> &'static str {
+        "ads"
+    }
+
+    fn label(&self) -> &'static str {
+        "ADS"
+    }
+
+    fn service(&self) -> &str {
+        "adsabs.harvard.edu"
+    }
+
+    fn recognises(&self, key: &str) -> Confidence {
+        certain_if(libads::validate_bib_code(key))
+    }
+
+    fn fetch(&self, key: &str) -> Result<String, InspirerError> {
+        if !libads::validate_bib_code(key) {
+            return Err(InspirerError::UnknownKeyFormat {
+                key: key.to_string(),
+            });
+        }
+
+        let mut url = self.base_url.clone();
+        url.query_pairs_mut()
+            .append_pair("data_type", "BIBTEX")
+            .append_pair("bibcode", key);
+        let data = http::get(&self.logger, url, key, "ADS")?;
+
+        // Without an entry, only the header is sent
+        data.split("\n@")
+            .nth(1)
+            .map(|entry| format!("@{}", entry))
+            .ok_or(InspirerError::NotFound {
+                key: key.to_string(),
+                source: "ADS",
+            })
+    }
+
+    fn journal_macros(&self) -> bool {
+        true
+    }
+}
+
+/// arXiv preprints, for identifiers such as `hep-th/9901001`, see `arxiv`
+pub struct Arxiv {
+    api: arxiv::Api,
+}
+
+impl Default for Arxiv {
+    fn default() -> Self {
+        Arxiv::with_client(inspire::Client::init(None))
+    }
+}
+
+impl Arxiv {
+    /// Look preprints up on INSPIRE with `client`, e.g. one shared with `Inspire`
+    pub fn with_client(client: inspire::Client) -> Self {
+        Arxiv {
+            api: arxiv::Api::init(None, client),
+        }
+    }
+
+    /// Never ask INSPIRE, see `arxiv::Api::without_inspire`
+    pub fn without_inspire(self) -> Self {
+        Arxiv {
+            api: self.api.without_inspire(),
+        }
+    }
+}
+
+impl Source for Arxiv {
+    fn name(&self) -> &'static str {
+        "arxiv"
+    }
+
+    fn label(&self) -> &'static str {
+        "arXiv"
+    }
+
+    // INSPIRE is asked first, but most of these requests end up at arXiv
+    fn service(&self) -> &str {
+        "arxiv.org"
+    }
+
+    fn recognises(&self, key: &str) -> Confidence {
+        certain_if(arxiv::validate_arxiv_id(key))
+    }
+
+    fn fetch(&self, key: &str) -> Result<String, InspirerError> {
+        let id = arxiv::ArxivId::new(key).map_err(|_| InspirerError::UnknownKeyFormat {
+            key: key.to_string(),
+        })?;
+
+        self.api.fetch_bibtex_with_key(id)
+    }
+}
+
+/// DOIs, such as `10.1103/PhysRevLett.116.061102`, see `doi`
+pub struct Doi {
+    api: doi::Api,
+}
+
+impl Default for Doi {
+    fn default() -> Self {
+        Doi::with_client(inspire::Client::init(None))
+    }
+}
+
+impl Doi {
+    /// Look DOIs up on INSPIRE with `client`, e.g. one shared with `Inspire`
+    pub fn with_client(client: inspire::Client) -> Self {
+        Doi {
+            api: doi::Api::init(None, client),
+        }
+    }
+
+    /// Never ask INSPIRE, see `doi::Api::without_inspire`
+    pub fn without_inspire(self) -> Self {
+        Doi {
+            api: self.api.without_inspire(),
+        }
+    }
+}
+
+impl Source for Doi {
+    fn name(&self) -> &'static str {
+        "doi"
+    }
+
+    fn label(&self) -> &'static str {
+        "DOI"
+    }
+
+    // As for arXiv, INSPIRE is asked first
+    fn service(&self) -> &str {
+        "doi.org"
+    }
+
+    fn recognises(&self, key: &str) -> Confidence {
+        certain_if(doi::validate_doi(key))
+    }
+
+    fn fetch(&self, key: &str) -> Result<String, InspirerError> {
+        let doi = doi::Doi::new(key).map_err(|_| InspirerError::UnknownKeyFormat {
+            key: key.to_string(),
+        })?;
+
+        self.api.fetch_bibtex_with_key(doi)
+    }
+}
+
+/// Local BibTeX databases, such as a bibliography shared by a group
+///
+/// Entries are copied verbatim, so that fixes made by hand are kept.  Keys are matched ignoring
+/// case, as BibTeX does; of entries with the same key, the one in the first database is used.
+/// Entries which depend on the rest of the databases come with what they use: the `@string`
+/// definitions of their abbreviations before them, and the entry named by `crossref` after them.
+/// These are copied verbatim too, see `bib::Entry::parse_with_dependencies`.
+///
+/// # Examples
+///
+/// ```
+/// use inspirer::source::{Confidence, Local, Source};
+///
+/// # let dir = std::env::temp_dir().join(format!("inspirer-local-{}", std::process::id()));
+/// # std::fs::create_dir_all(&dir).unwrap();
+/// let path = dir.join("master.bib");
+/// std::fs::write(&path, "@article{Higgs:2014aqa,\n  title = {Fixed by hand}\n}\n").unwrap();
+///
+/// let local = Local::open(&[&path]).unwrap();
+///
+/// assert_eq!(local.recognises("Higgs:2014aqa"), Confidence::Certain);
+/// assert_eq!(local.recognises("higgs:2014AQA"), Confidence::Certain);
+/// assert_eq!(local.recognises("Higgs:2015mei"), Confidence::No);
+/// assert_eq!(
+///     local.fetch("Higgs:2014aqa").unwrap(),
+///     "@article{Higgs:2014aqa,\n  title = {Fixed by hand}\n}\n"
+/// );
+/// # std::fs::remove_dir_all(&dir).unwrap();
+/// ```
+pub struct Local {
+    /// Entries with the text they depend on, by lowercased key
+    entries: HashMap<String, String>,
+}
+
+impl Local {
+    /// Read the entries of BibTeX databases
+    pub fn open<P: AsRef<Path>>(paths: &[P]) -> Result<Self, InspirerError> {
+        let mut entries = HashMap::new();
+        let mut strings = Vec::new();
+        for path in paths {
+            let database = fs::read_to_string(path).map_err(InspirerError::Io)?;
+            for bib::EntrySpan { key, span } in bib::entries(&database) {
+                entries
+                    .entry(key.to_lowercase())
+                    .or_insert_with(|| format!("{}\n", &database[span]));
+            }
+            for string in bib::string_spans(&database) {
+                let text = format!("{}\n", &database[string.span.clone()]);
+                strings.push((string, text));
+            }
+        }
+
+        let entries = entries
+            .iter()
+            .map(|(key, text)| (key.clone(), with_dependencies(text, &strings, &entries)))
+            .collect();
+
+        Ok(Local { entries })
+    }
+}
+
+/// `text` preceded by the `@string` definitions among `strings` it uses and followed by its
+/// `crossref` parent among `entries`, or unchanged if it uses neither
+fn with_dependencies(
+    text: &str,
+    strings: &[(bib::StringSpan, String)],
+    entries: &HashMap<String, String>,
+) -> String {
+    let entry = match bib::Entry::parse(text) {
+        Ok(entry) => entry,
+        // Reported when the entry is fetched
+        Err(_) => return text.to_string(),
+    };
+
+    let parent = entry
+        .get("crossref")
+        .and_then(bib::Value::as_str)
+        .map(str::to_lowercase)
+        .filter(|parent| *parent != entry.key().to_lowercase())
+        .and_then(|parent| entries.get(&parent));
+    let parent_entry = parent.and_then(|parent| bib::Entry::parse(parent).ok());
+
+    // Each abbreviation is looked up among the definitions before its use, and those definitions
+    // may use abbreviations in turn
+    let mut uses: Vec<(&str, usize)> = entry
+        .fields()
+        .iter()
+        .chain(parent_entry.iter().flat_map(|parent| parent.fields()))
+        .flat_map(|field| field.value.macros())
+        .map(|name| (name, strings.len()))
+        .collect();
+    let mut used = Vec::new();
+    while let Some((name, before)) = uses.pop() {
+        let definition = strings[..before]
+            .iter()
+            .rposition(|(string, _)| string.name.eq_ignore_ascii_case(name));
+        if let Some(i) = definition.filter(|i| !used.contains(i)) {
+            used.push(i);
+            uses.extend(
+                strings[i]
+                    .0
+                    .value
+                    .macros()
+                    .into_iter()
+                    .map(|name| (name, i)),
+            );
+        }
+    }
+    used.sort_unstable();
+
+    let mut text_with_dependencies: String = used.iter().map(|&i| strings[i].1.as_str()).collect();
+    text_with_dependencies.push_str(text);
+    if let Some(parent) = parent {
+        text_with_dependencies.push_str(parent);
+    }
+
+    text_with_dependencies
+}
+
+impl Source for Local {
+    fn name(&self) -> &'static str {
+        "local"
+    }
+
+    fn label(&self) -> &'static str {
+        "local"
+    }
+
+    fn service(&self) -> &str {
+        "local"
+    }
+
+    fn recognises(&self, key: &str) -> Confidence {
+        certain_if(self.entries.contains_key(&key.to_lowercase()))
+    }
+
+    fn fetch(&self, key: &str) -> Result<String, InspirerError> {
+        self.entries
+            .get(&key.to_lowercase())
+            .cloned()
+            .ok_or(InspirerError::NotFound {
+                key: key.to_string(),
+                source: "local",
+            })
+    }
+
+    fn local(&self) -> bool {
+        true
+    }
+}
+
+/// The built-in sources, in the order they are asked
+pub fn builtin() -> Vec<Box<dyn Source>> {
+    builtin_with_client(inspire::Client::init(None))
+}
+
+/// The built-in sources, looking records up on INSPIRE with `client`
+pub fn builtin_with_client(client: inspire::Client) -> Vec<Box<dyn Source>> {
+    builtin_asking_inspire(client, true)
+}
+
+/// The built-in sources, of which arXiv and DOIs ask INSPIRE first only if `ask_inspire` is set
+pub(crate) fn builtin_asking_inspire(
+    client: inspire::Client,
+    ask_inspire: bool,
+) -> Vec<Box<dyn Source>> {
+    let mut arxiv = Arxiv::with_client(client.clone());
+    let mut doi = Doi::with_client(client.clone());
+    if !ask_inspire {
+        arxiv = arxiv.without_inspire();
+        doi = doi.without_inspire();
+    }
+
+    vec![
+        Box::new(Inspire::with_client(client)),
+        Box::new(Ads::default()),
+        Box::new(arxiv),
+        Box::new(doi),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recognises() {
+        let keys = [
+            "Randall:1999ee",
+            "1999PhRvL..83.3370R",
+            "hep-th/9901001",
+            "10.1103/PhysRevLett.116.061102",
+        ];
+
+        for (i, source) in builtin().iter().enumerate() {
+            for (j, key) in keys.iter().enumerate() {
+                let expected = if i == j {
+                    Confidence::Certain
+                } else {
+                    Confidence::No
+                };
+                assert_eq!(
+                    source.recognises(key),
+                    expected,
+                    "{} {}",
+                    source.name(),
+                    key
+                );
+            }
+            assert_eq!(source.recognises("not a key"), Confidence::No);
+        }
+    }
+
+    #[test]
+    fn test_confidence_order() {
+        assert!(Confidence::No < Confidence::Maybe);
+        assert!(Confidence::Maybe < Confidence::Certain);
+    }
+
+    /// Answer one request with a stub server, sending back the request line
+    fn serve_once(body: &'static str) -> (Url, std::sync::mpsc::Receiver<String>) {
+        use std::io::{BufRead, BufReader, Write};
+
+        let listener =
+            std::net::TcpListener::bind("127.0.0.1:0").expect("can bind to a local port");
+        let url = Url::parse(&format!(
+            "http://{}/nph-bib_query/",
+            listener.local_addr().expect("listener has an address")
+        ))
+        .unwrap();
+        let (sender, receiver) = std::sync::mpsc::channel();
+
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().expect("client connects");
+            let mut reader = BufReader::new(stream.try_clone().expect("can clone stream"));
+            let mut line = String::new();
+            reader.read_line(&mut line).expect("can read request");
+            let _ = sender.send(line.clone());
+            while line != "\r\n" {
+                line.clear();
+                reader.read_line(&mut line).expect("can read request");
+            }
+
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            )
+            .expect("can write response");
+        });
+
+        (url, receiver)
+    }
+
+    #[test]
+    fn test_ads() {
+        let (url, requests) = serve_once(
+            "Query Results from the ADS Database\n\n\n@ARTICLE{1999PhRvL..83.3370R,\n}\n",
+        );
+
+        assert_eq!(
+            Ads::with_base_url(url)
+                .fetch("1999PhRvL..83.3370R")
+                .unwrap(),
+            "@ARTICLE{1999PhRvL..83.3370R,\n}\n"
+        );
+        assert_eq!(
+            requests.recv().unwrap(),
+            "GET /nph-bib_query/?data_type=BIBTEX&bibcode=1999PhRvL..83.3370R HTTP/1.1\r\n"
+        );
+
+        let (url, _) = serve_once("Query Results from the ADS Database\n\nNo records found\n");
+        match Ads::with_base_url(url).fetch("1999PhRvL..83.3370R") {
+            Err(InspirerError::NotFound { source: "ADS", .. }) => {}
+            r => panic!("unexpected result {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_inspire_malformed_response() {
+        let (url, requests) = serve_once("<html>Not BibTeX</html>");
+        let client = inspire::Client::init(None).with_base_url(url);
+
+        match Inspire::with_client(client).fetch("1999PhRvL..83.3370R") {
+            Err(InspirerError::MalformedResponse { url, .. }) => {
+                let request = requests.recv().unwrap();
+                assert!(url.contains("/nph-bib_query/literature?q="));
+                assert!(request.contains(&url[url.find("/nph-bib_query/").unwrap()..]));
+            }
+            r => panic!("unexpected result {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_ads_network_unavailable() {
+        // Nothing listens on the port once the listener is dropped
+        let url = {
+            let listener =
+                std::net::TcpListener::bind("127.0.0.1:0").expect("can bind to a local port");
+            Url::parse(&format!(
+                "http://{}/nph-bib_query/",
+                listener.local_addr().expect("listener has an address")
+            ))
+            .unwrap()
+        };
+
+        let result = Ads::with_base_url(url).fetch("1999PhRvL..83.3370R");
+
+        match result {
+            Err(InspirerError::Network { status: None, .. }) => {}
+            ref r => panic!("unexpected result {:?}", r),
+        }
+        let report = crate::report::Report::new(
+            &crate::Inspirer::init(None),
+            &["1999PhRvL..83.3370R"],
+            &[result.map(|text| (text, "ads"))],
+        );
+        assert_eq!(
+            report.exit_code(false),
+            crate::report::EXIT_NETWORK_UNAVAILABLE
+        );
+    }
+
+    #[test]
+    fn test_local() {
+        let tmp_dir = assert_fs::TempDir::new().expect("can create tmp_dir");
+        let master = tmp_dir.path().join("master.bib");
+        let other = tmp_dir.path().join("other.bib");
+        fs::write(
+            &master,
+            "@string{prl = {Phys. Rev. Lett.}}\n@string{unused = {Unused}}\n@string{prlong = prl # { (Long)}}\n\n@ARTICLE{Randall:1999ee,\n  journal = prlong,\n  crossref = {weinberg}\n}\n@article{Higgs:2014aqa,\n  Year   =   2014\n}\n",
+        )
+        .unwrap();
+        fs::write(
+            &other,
+            "@article{Randall:1999ee}\n@book{Weinberg, title = {QFT}, journal = {Nature}, month = jun}",
+        )
+        .unwrap();
+
+        let local = Local::open(&[&master, &other]).unwrap();
+
+        assert!(local.local());
+        // Abbreviations are defined before the entry and the crossref parent comes after it
+        assert_eq!(
+            local.fetch("Randall:1999ee").unwrap(),
+            "@string{prl = {Phys. Rev. Lett.}}\n\
+             @string{prlong = prl # { (Long)}}\n\
+             @ARTICLE{Randall:1999ee,\n  journal = prlong,\n  crossref = {weinberg}\n}\n\
+             @book{Weinberg, title = {QFT}, journal = {Nature}, month = jun}\n"
+        );
+        assert_eq!(local.recognises("randall:1999EE"), Confidence::Certain);
+        assert_eq!(
+            local.fetch("randall:1999EE").unwrap(),
+            local.fetch("Randall:1999ee").unwrap()
+        );
+        assert_eq!(
+            local.fetch("Higgs:2014aqa").unwrap(),
+            "@article{Higgs:2014aqa,\n  Year   =   2014\n}\n"
+        );
+        assert_eq!(
+            local.fetch("Weinberg").unwrap(),
+            "@book{Weinberg, title = {QFT}, journal = {Nature}, month = jun}\n"
+        );
+        assert_eq!(local.recognises("prl"), Confidence::No);
+        assert!(local.fetch("Higgs:2015mei").is_err());
+
+        assert!(Local::open(&[tmp_dir.path().join("missing.bib")]).is_err());
+    }
+
+    #[test]
+    fn test_fetch_unrecognised() {
+        match Inspire::default().fetch("not a key") {
+            Err(InspirerError::UnknownKeyFormat { key }) => assert_eq!(key, "not a key"),
+            _ => panic!("key is not fetched"),
+        }
+    }
+}
@@ -0,0 +1,417 @@
+//! Extracting citations from LaTeX sources
+//!
+//! This allows a bibliography to be fetched before LaTeX has been run successfully.  Citation
+//! commands from plain LaTeX, natbib and biblatex are understood, including biblatex's multicite
+//! commands and optional pre- and postnotes.
+
+/// Citation commands which take a single list of keys
+const CITE_COMMANDS: &[&str] = &[
+    // LaTeX
+    "cite",
+    "nocite",
+    // natbib
+    "citep",
+    "citet",
+    "citealp",
+    "citealt",
+    "citeauthor",
+    "citefullauthor",
+    "citeyear",
+    "citeyearpar",
+    "citenum",
+    "Citep",
+    "Citet",
+    "Citealp",
+    "Citealt",
+    "Citeauthor",
+    // biblatex
+    "Cite",
+    "autocite",
+    "Autocite",
+    "parencite",
+    "Parencite",
+    "textcite",
+    "Textcite",
+    "footcite",
+    "footcitetext",
+    "smartcite",
+    "Smartcite",
+    "supercite",
+    "fullcite",
+    "footfullcite",
+    "citetitle",
+    "citedate",
+    "citeurl",
+];
+
+/// biblatex multicite commands, which take any number of key lists
+const MULTICITE_COMMANDS: &[&str] = &[
+    "cites",
+    "Cites",
+    "autocites",
+    "Autocites",
+    "parencites",
+    "Parencites",
+    "textcites",
+    "Textcites",
+    "footcites",
+    "footcitetexts",
+    "smartcites",
+    "Smartcites",
+    "supercites",
+];
+
+/// A citation in a .tex file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Citation {
+    pub key: String,
+    /// The command used, without the leading backslash
+    pub command: String,
+    /// Line of the .tex file on which the citation appears, starting from 1
+    pub line: usize,
+}
+
+/// The citations and includes of a LaTeX source file
+///
+/// # Examples
+///
+/// ```
+/// use inspirer::tex::TexDocument;
+///
+/// let tex = TexDocument::parse(
+/// r"\input{preamble}
+/// As shown by \citet[p.~3]{Higgs:2014aqa} and \parencites(see)()[ch. 2]{Guth:1980zm}{Linde:1981mu}.
+/// % \cite{Albrecht:1982wi}
+/// \include{chapter1}");
+///
+/// assert_eq!(tex.keys(), vec!["Guth:1980zm", "Higgs:2014aqa", "Linde:1981mu"]);
+/// assert_eq!(tex.inputs, vec!["preamble", "chapter1"]);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TexDocument {
+    /// Citations in the order they appear
+    pub citations: Vec<Citation>,
+    /// Files included with `\input` or `\include`, as written
+    pub inputs: Vec<String>,
+}
+
+impl TexDocument {
+    /// Parse a LaTeX source file
+    ///
+    /// Commented-out text is ignored.  Parsing never fails; anything which is not understood is
+    /// skipped.
+    pub fn parse(input: &str) -> Self {
+        let source = strip_comments(input);
+        let mut scanner = Scanner::new(&source);
+        let mut doc = TexDocument::default();
+
+        while let Some(c) = scanner.bump() {
+            if c != '\\' {
+                continue;
+            }
+
+            let line = scanner.line;
+            let name = scanner.letters();
+            if name.is_empty() {
+                // Control symbol, such as \\ or \%
+                scanner.bump();
+                continue;
+            }
+
+            let mut key_lists = Vec::new();
+            if CITE_COMMANDS.contains(&name.as_str()) {
+                scanner.eat('*');
+                scanner.optional_arguments('[', ']', 2);
+                if let Some(keys) = scanner.group() {
+                    key_lists.push(keys);
+                }
+            } else if MULTICITE_COMMANDS.contains(&name.as_str()) {
+                scanner.optional_arguments('(', ')', 2);
+                loop {
+                    scanner.optional_arguments('[', ']', 2);
+                    match scanner.group() {
+                        Some(keys) => key_lists.push(keys),
+                        None => break,
+                    }
+                    // Further key lists follow on the same line, so that braced text after the
+                    // citation is not taken for keys
+                    scanner.same_line = true;
+                }
+                scanner.same_line = false;
+            } else if name == "input" || name == "include" {
+                if let Some(file) = scanner.group() {
+                    doc.inputs.push(file.trim().to_string());
+                }
+            }
+
+            for keys in key_lists {
+                doc.citations.extend(
+                    keys.split(',')
+                        .map(|k| k.trim())
+                        // \nocite{*} adds the whole database rather than a key
+                        .filter(|k| !k.is_empty() && *k != "*")
+                        .map(|k| Citation {
+                            key: k.to_string(),
+                            command: name.clone(),
+                            line,
+                        }),
+                );
+            }
+        }
+
+        doc
+    }
+
+    /// Cited keys, sorted alphabetically and without duplicates
+    pub fn keys(&self) -> Vec<String> {
+        let mut keys: Vec<String> = self.citations.iter().map(|c| c.key.clone()).collect();
+
+        keys.sort_unstable();
+        keys.dedup();
+
+        keys
+    }
+}
+
+/// Remove comments, keeping line breaks so that line numbers are preserved
+fn strip_comments(input: &str) -> String {
+    input
+        .lines()
+        .map(|line| {
+            let mut escaped = false;
+            for (i, c) in line.char_indices() {
+                match c {
+                    '%' if !escaped => return &line[..i],
+                    '\\' => escaped = !escaped,
+                    _ => escaped = false,
+                }
+            }
+            line
+        })
+        .collect::<Vec<&str>>()
+        .join("\n")
+}
+
+struct Scanner<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+    line: usize,
+    /// Line breaks since the last character other than whitespace
+    line_breaks: usize,
+    /// Whether arguments must be on the same line as what precedes them
+    same_line: bool,
+}
+
+impl<'a> Scanner<'a> {
+    fn new(input: &'a str) -> Self {
+        Scanner {
+            chars: input.chars().peekable(),
+            line: 1,
+            line_breaks: 0,
+            same_line: false,
+        }
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.chars.next();
+        match c {
+            Some('\n') => {
+                self.line += 1;
+                self.line_breaks += 1;
+            }
+            Some(c) if !c.is_whitespace() => self.line_breaks = 0,
+            _ => {}
+        }
+        c
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.chars.peek() == Some(&c) {
+            self.bump();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Skip whitespace between arguments
+    ///
+    /// A blank line ends a command, as it does for TeX, so is not skipped; nor is a line break
+    /// with `same_line`.
+    fn skip_whitespace(&mut self) {
+        while let Some(&c) = self.chars.peek() {
+            if !c.is_whitespace() || (c == '\n' && (self.same_line || self.line_breaks > 0)) {
+                break;
+            }
+            self.bump();
+        }
+    }
+
+    /// Read the letters of a control sequence name
+    fn letters(&mut self) -> String {
+        let mut name = String::new();
+
+        while let Some(&c) = self.chars.peek() {
+            if !c.is_ascii_alphabetic() {
+                break;
+            }
+            name.push(c);
+            self.bump();
+        }
+
+        name
+    }
+
+    /// Read text up to an unbalanced `close`, keeping nested brace groups intact
+    fn delimited(&mut self, close: char) -> String {
+        let mut content = String::new();
+        let mut depth = 0;
+
+        while let Some(c) = self.bump() {
+            match c {
+                '\\' => {
+                    content.push(c);
+                    if let Some(escaped) = self.bump() {
+                        content.push(escaped);
+                    }
+                    continue;
+                }
+                '{' => depth += 1,
+                '}' if depth > 0 => depth -= 1,
+                c if c == close && depth == 0 => break,
+                _ => {}
+            }
+            content.push(c);
+        }
+
+        content
+    }
+
+    /// Skip up to `n` optional arguments delimited by `open` and `close`, such as pre- and
+    /// postnotes
+    fn optional_arguments(&mut self, open: char, close: char, n: usize) {
+        for _ in 0..n {
+            self.skip_whitespace();
+            if !self.eat(open) {
+                break;
+            }
+            self.delimited(close);
+        }
+    }
+
+    /// Read a mandatory argument in braces
+    fn group(&mut self) -> Option<String> {
+        self.skip_whitespace();
+        if self.eat('{') {
+            Some(self.delimited('}'))
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(input: &str) -> Vec<String> {
+        TexDocument::parse(input).keys()
+    }
+
+    #[test]
+    fn test_cite() {
+        assert_eq!(
+            keys(r"\cite{Higgs:2014aqa} \cite{Higgs:2015mei,Guth:1980zm}"),
+            vec!["Guth:1980zm", "Higgs:2014aqa", "Higgs:2015mei"]
+        );
+    }
+
+    #[test]
+    fn test_natbib() {
+        assert_eq!(
+            keys(
+                r"\citep[see][p.~3]{Higgs:2014aqa} \citet*{Guth:1980zm} \citeauthor{Linde:1981mu}"
+            ),
+            vec!["Guth:1980zm", "Higgs:2014aqa", "Linde:1981mu"]
+        );
+    }
+
+    #[test]
+    fn test_biblatex() {
+        assert_eq!(
+            keys(
+                r"\autocite[42]{Higgs:2014aqa} \Textcite{Guth:1980zm} \parencite[{see [1]}]{Linde:1981mu}"
+            ),
+            vec!["Guth:1980zm", "Higgs:2014aqa", "Linde:1981mu"]
+        );
+    }
+
+    #[test]
+    fn test_multicite() {
+        let tex = TexDocument::parse(
+            r"\textcites(Global pre)(global post)[pre][post]{Higgs:2014aqa} [p.~5]{Guth:1980zm}  {Linde:1981mu}
+              {not a key} and more text {not a key}",
+        );
+
+        assert_eq!(
+            tex.keys(),
+            vec!["Guth:1980zm", "Higgs:2014aqa", "Linde:1981mu"]
+        );
+        assert!(tex.citations.iter().all(|c| c.command == "textcites"));
+    }
+
+    #[test]
+    fn test_multicite_ends_at_text() {
+        assert_eq!(
+            keys(r"\cites{Higgs:2014aqa}[p.~5]{Guth:1980zm} shows"),
+            vec!["Guth:1980zm", "Higgs:2014aqa"]
+        );
+    }
+
+    #[test]
+    fn test_arguments_across_lines() {
+        // A single line break may separate a command from its argument, a blank line may not
+        assert_eq!(
+            keys("\\cite[p.~3]\n  {Higgs:2014aqa} \\cite\n\n{not a key}"),
+            vec!["Higgs:2014aqa"]
+        );
+        assert_eq!(
+            keys("\\cites{Higgs:2014aqa}\n{not a key} \\cites[p.~5]\n{Guth:1980zm}"),
+            vec!["Guth:1980zm", "Higgs:2014aqa"]
+        );
+    }
+
+    #[test]
+    fn test_comments() {
+        let tex = TexDocument::parse(
+            "\\cite{Higgs:2014aqa} % \\cite{Guth:1980zm}\n% \\cite{Linde:1981mu}\n50\\% \\cite{Higgs:2015mei}",
+        );
+
+        assert_eq!(tex.keys(), vec!["Higgs:2014aqa", "Higgs:2015mei"]);
+        assert_eq!(tex.citations[1].line, 3);
+    }
+
+    #[test]
+    fn test_not_citations() {
+        assert!(keys(r"\citestyle{aa} \nocite{*} \citetext{priv. comm.} \excite{x}").is_empty());
+    }
+
+    #[test]
+    fn test_inputs() {
+        let tex = TexDocument::parse("\\input{intro}\n\\include{ chapters/one }\n% \\input{old}");
+
+        assert_eq!(tex.inputs, vec!["intro", "chapters/one"]);
+    }
+
+    #[test]
+    fn test_example_biber() {
+        assert_eq!(
+            keys(include_str!("../example_files/test_biber.tex")),
+            vec![
+                "1982PhRvL..48.1220A",
+                "Albrecht:1982wi",
+                "Guth:1980zm",
+                "Linde:1981mu"
+            ]
+        );
+    }
+}
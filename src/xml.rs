@@ -0,0 +1,300 @@
+//! Reading XML documents
+//!
+//! arXiv's Atom feeds and biber's control files are read by looking elements up by name, which
+//! needs no more than a tree of elements, their attributes and their text.  Parsing is lenient:
+//! comments, processing instructions and declarations are skipped, and unbalanced tags close the
+//! elements they can.
+
+/// An element of an XML document
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Element {
+    /// The name as written, including any namespace prefix such as `bcf:`
+    pub name: String,
+    attrs: Vec<(String, String)>,
+    children: Vec<Child>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Child {
+    Element(Element),
+    Text(String),
+}
+
+impl Element {
+    /// Parse a document, giving an element without a name which holds its top-level elements
+    pub fn parse(input: &str) -> Self {
+        let mut stack = vec![Element::default()];
+
+        let mut pos = 0;
+        while pos < input.len() {
+            let rest = &input[pos..];
+            if !rest.starts_with('<') {
+                let end = rest.find('<').unwrap_or(rest.len());
+                push_child(&mut stack, Child::Text(unescape(&rest[..end])));
+                pos += end;
+            } else if rest.starts_with("<![CDATA[") {
+                let end = rest.find("]]>").unwrap_or(rest.len());
+                push_child(&mut stack, Child::Text(rest[9..end].to_string()));
+                pos += (end + 3).min(rest.len());
+            } else if rest.starts_with("<!--") {
+                pos += rest.find("-->").map_or(rest.len(), |end| end + 3);
+            } else if rest.starts_with("<?") || rest.starts_with("<!") {
+                pos += rest.find('>').map_or(rest.len(), |end| end + 1);
+            } else if rest.starts_with("</") {
+                let end = rest.find('>').unwrap_or(rest.len());
+                close(&mut stack, rest[2..end].trim());
+                pos += (end + 1).min(rest.len());
+            } else {
+                let end = tag_end(rest);
+                let tag = &rest[1..end];
+                let (tag, empty) = match tag.strip_suffix('/') {
+                    Some(tag) => (tag, true),
+                    None => (tag, false),
+                };
+                let name_end = tag.find(|c: char| c.is_whitespace()).unwrap_or(tag.len());
+                let element = Element {
+                    name: tag[..name_end].to_string(),
+                    attrs: attributes(&tag[name_end..]),
+                    children: Vec::new(),
+                };
+                if empty {
+                    push_child(&mut stack, Child::Element(element));
+                } else {
+                    stack.push(element);
+                }
+                pos += (end + 1).min(rest.len());
+            }
+        }
+
+        // Close the elements which were left open
+        while stack.len() > 1 {
+            let element = stack.pop().expect("stack is not empty");
+            push_child(&mut stack, Child::Element(element));
+        }
+
+        stack.pop().expect("document element is on the stack")
+    }
+
+    /// The elements inside this one called `name`, at any depth, in document order
+    pub fn find(&self, name: &str) -> Vec<&Element> {
+        let mut found = Vec::new();
+        self.find_into(name, &mut found);
+
+        found
+    }
+
+    fn find_into<'a>(&'a self, name: &str, found: &mut Vec<&'a Element>) {
+        for child in &self.children {
+            if let Child::Element(ref element) = *child {
+                if element.name == name {
+                    found.push(element);
+                }
+                element.find_into(name, found);
+            }
+        }
+    }
+
+    /// The value of an attribute
+    pub fn attr(&self, name: &str) -> Option<&str> {
+        self.attrs
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// The text inside the element, including that of the elements inside it
+    pub fn text(&self) -> String {
+        let mut text = String::new();
+        self.text_into(&mut text);
+
+        text
+    }
+
+    fn text_into(&self, text: &mut String) {
+        for child in &self.children {
+            match *child {
+                Child::Element(ref element) => element.text_into(text),
+                Child::Text(ref t) => text.push_str(t),
+            }
+        }
+    }
+}
+
+fn push_child(stack: &mut [Element], child: Child) {
+    stack
+        .last_mut()
+        .expect("document element is on the stack")
+        .children
+        .push(child);
+}
+
+/// Close the innermost open element called `name` and those inside it
+///
+/// An end tag without a matching start tag is ignored.
+fn close(stack: &mut Vec<Element>, name: &str) {
+    if !stack[1..].iter().any(|e| e.name == name) {
+        return;
+    }
+    loop {
+        let element = stack.pop().expect("open element is on the stack");
+        let matched = element.name == name;
+        push_child(stack, Child::Element(element));
+        if matched {
+            break;
+        }
+    }
+}
+
+/// Position of the `>` ending the tag at the start of `rest`, ignoring any in quoted values
+fn tag_end(rest: &str) -> usize {
+    let mut quote = None;
+    for (i, c) in rest.char_indices() {
+        match (quote, c) {
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (Some(q), c) if c == q => quote = None,
+            (None, '>') => return i,
+            _ => {}
+        }
+    }
+
+    rest.len()
+}
+
+/// The attributes of a start tag, such as `type="file" datatype='bibtex'`
+fn attributes(text: &str) -> Vec<(String, String)> {
+    let mut attrs = Vec::new();
+
+    let mut rest = text;
+    while let Some(eq) = rest.find('=') {
+        let name = rest[..eq].trim();
+        let value = rest[eq + 1..].trim_start();
+        let quote = match value.chars().next() {
+            Some(q) if q == '"' || q == '\'' => q,
+            _ => break,
+        };
+        let end = match value[1..].find(quote) {
+            Some(end) => end + 1,
+            None => break,
+        };
+        attrs.push((name.to_string(), unescape(&value[1..end])));
+        rest = &value[end + 1..];
+    }
+
+    attrs
+}
+
+/// Replace character and entity references by the characters they stand for
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+
+    let mut rest = text;
+    while let Some(amp) = rest.find('&') {
+        unescaped.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let c = rest.find(';').and_then(|semi| {
+            let c = match &rest[1..semi] {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                reference => match reference.strip_prefix('#') {
+                    Some(hex) if hex.starts_with('x') || hex.starts_with('X') => {
+                        u32::from_str_radix(&hex[1..], 16).ok()
+                    }
+                    Some(decimal) => decimal.parse().ok(),
+                    None => None,
+                }
+                .and_then(std::char::from_u32),
+            };
+            c.map(|c| (c, semi))
+        });
+        match c {
+            Some((c, semi)) => {
+                unescaped.push(c);
+                rest = &rest[semi + 1..];
+            }
+            // A lone `&` is kept
+            None => {
+                unescaped.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    unescaped.push_str(rest);
+
+    unescaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find() {
+        let document = Element::parse(
+            r#"<?xml version="1.0"?>
+<!-- <entry>commented</entry> -->
+<feed>
+  <entry><id>1</id><entry><id>2</id></entry></entry>
+  <entry/>
+</feed>"#,
+        );
+
+        let entries = document.find("entry");
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].text(), "12");
+        assert_eq!(entries[1].text(), "2");
+        assert_eq!(entries[2].text(), "");
+        assert_eq!(
+            document
+                .find("id")
+                .iter()
+                .map(|e| e.text())
+                .collect::<Vec<String>>(),
+            vec!["1", "2"]
+        );
+        assert!(document.find("missing").is_empty());
+    }
+
+    #[test]
+    fn test_attr() {
+        let document = Element::parse(
+            r#"<bcf:datasource type="file" datatype='bibtex' note="a &gt; b">refs.bib</bcf:datasource>"#,
+        );
+        let datasource = document.find("bcf:datasource")[0];
+
+        assert_eq!(datasource.attr("type"), Some("file"));
+        assert_eq!(datasource.attr("datatype"), Some("bibtex"));
+        assert_eq!(datasource.attr("note"), Some("a > b"));
+        assert_eq!(datasource.attr("missing"), None);
+        assert_eq!(datasource.text(), "refs.bib");
+    }
+
+    #[test]
+    fn test_text() {
+        let document = Element::parse(
+            "<title>Fish &amp; Chips &#8211; &#x2014; <![CDATA[<raw> & more]]> &unknown; AT&T</title>",
+        );
+
+        assert_eq!(
+            document.find("title")[0].text(),
+            "Fish & Chips \u{2013} \u{2014} <raw> & more &unknown; AT&T"
+        );
+    }
+
+    #[test]
+    fn test_unbalanced() {
+        let document = Element::parse("<a><b>one</a></c><b>two");
+
+        assert_eq!(document.find("a")[0].text(), "one");
+        assert_eq!(
+            document
+                .find("b")
+                .iter()
+                .map(|e| e.text())
+                .collect::<Vec<String>>(),
+            vec!["one", "two"]
+        );
+    }
+}
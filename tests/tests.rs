@@ -5,7 +5,9 @@ use std::process::Command;
 
 use assert_cmd::prelude::*;
 use assert_fs::prelude::*;
+use inspirer::cache::Cache;
 use nom_bibtex::Bibtex;
+use serde_json::json;
 
 mod text;
 
@@ -139,6 +141,20 @@ fn blg2bib_runs() {
     cmd.assert().success();
 }
 
+#[test]
+fn log2bib_runs() {
+    let mut cmd = Command::cargo_bin("log2bib").unwrap();
+    cmd.arg("--help");
+    cmd.assert().success();
+}
+
+#[test]
+fn tex2bib_runs() {
+    let mut cmd = Command::cargo_bin("tex2bib").unwrap();
+    cmd.arg("--help");
+    cmd.assert().success();
+}
+
 #[test]
 fn aux2bib_stdin_stdout_empty() {
     let mut cmd = Command::cargo_bin("aux2bib").unwrap();
@@ -155,6 +171,518 @@ fn blg2bib_stdin_stdout_empty() {
     cmd.assert().success().stdout("");
 }
 
+#[test]
+fn log2bib_stdin_stdout_empty() {
+    let mut cmd = Command::cargo_bin("log2bib").unwrap();
+    cmd.with_stdin().buffer("");
+
+    cmd.assert().success().stdout("");
+}
+
+#[test]
+fn tex2bib_stdin_stdout_empty() {
+    let mut cmd = Command::cargo_bin("tex2bib").unwrap();
+    cmd.with_stdin().buffer("");
+
+    cmd.assert().success().stdout("");
+}
+
+#[test]
+fn tex2bib_stdin_stdout_strict() {
+    // Keys of unknown format are only unresolved with --strict
+    let mut cmd = Command::cargo_bin("tex2bib").unwrap();
+    cmd.with_stdin()
+        .buffer("\\cite{Einstein1905}\n")
+        .assert()
+        .success()
+        .stdout("");
+
+    let mut cmd = Command::cargo_bin("tex2bib").unwrap();
+    cmd.arg("--strict")
+        .with_stdin()
+        .buffer("\\cite{Einstein1905}\n")
+        .assert()
+        .code(2)
+        .stdout("");
+}
+
+#[test]
+fn inspirer_cache_runs() {
+    let mut cmd = Command::cargo_bin("inspirer-cache").unwrap();
+    cmd.arg("--help");
+    cmd.assert().success();
+}
+
+#[test]
+fn inspirer_cache_list_clear() {
+    let tmp_dir = assert_fs::TempDir::new().expect("can create tmp_dir");
+    tmp_dir
+        .child("inspire")
+        .child("Higgs%3A2014aqa.bib")
+        .write_str("@article{Higgs:2014aqa,\n}\n")
+        .unwrap();
+    tmp_dir
+        .child("arxiv")
+        .child("hep-th%2F9901001.bib")
+        .write_str("@article{hep-th/9901001,\n}\n")
+        .unwrap();
+
+    let mut cmd = Command::cargo_bin("inspirer-cache").unwrap();
+    cmd.arg("--dir").arg(tmp_dir.path()).arg("list");
+    cmd.assert()
+        .success()
+        .stdout("arxiv\thep-th/9901001\t0 days\ninspire\tHiggs:2014aqa\t0 days\n");
+
+    let mut cmd = Command::cargo_bin("inspirer-cache").unwrap();
+    cmd.arg("--dir").arg(tmp_dir.path()).arg("prune");
+    cmd.assert().success();
+    assert!(tmp_dir
+        .path()
+        .join("inspire")
+        .join("Higgs%3A2014aqa.bib")
+        .exists());
+
+    let mut cmd = Command::cargo_bin("inspirer-cache").unwrap();
+    cmd.arg("--dir").arg(tmp_dir.path()).arg("clear");
+    cmd.assert().success();
+
+    let mut cmd = Command::cargo_bin("inspirer-cache").unwrap();
+    cmd.arg("--dir").arg(tmp_dir.path()).arg("list");
+    cmd.assert().success().stdout("");
+}
+
+/// A temporary directory to use as `XDG_CACHE_HOME`, caching each `(source, key, entry)`
+fn cache_dir(entries: &[(&str, &str, &str)]) -> assert_fs::TempDir {
+    let tmp_dir = assert_fs::TempDir::new().expect("can create tmp_dir");
+    let cache = Cache::new(tmp_dir.path().join("inspirer"));
+    for (source, key, entry) in entries {
+        cache.put(source, key, entry).expect("can cache entry");
+    }
+
+    tmp_dir
+}
+
+/// Run `bin` offline, with the cache in `cache_dir`
+fn offline(bin: &str, cache_dir: &assert_fs::TempDir) -> Command {
+    let mut cmd = Command::cargo_bin(bin).unwrap();
+    cmd.env("XDG_CACHE_HOME", cache_dir.path()).arg("--offline");
+
+    cmd
+}
+
+#[test]
+fn tex2bib_file_stdout_offline() {
+    let tmp_dir = cache_dir(&[("inspire", "Higgs:2014aqa", "@article{Higgs:2014aqa,\n}\n")]);
+
+    let mut cmd = offline("tex2bib", &tmp_dir);
+    cmd.arg(Path::new("example_files").join("test_bibtex.tex"));
+
+    // Higgs:2015mei is not cached, so is left out
+    cmd.assert().code(2).stdout("@article{Higgs:2014aqa,\n}\n");
+}
+
+#[test]
+fn tex2bib_file_stdout_offline_format() {
+    let tmp_dir = cache_dir(&[(
+        "inspire",
+        "Higgs:2014aqa",
+        "@ARTICLE{Higgs:2014aqa,\n   YEAR = 2014,\n  author = \"Higgs, Peter W.\"\n}\n",
+    )]);
+
+    let mut cmd = offline("tex2bib", &tmp_dir);
+    cmd.args(["--format", "--indent", "4"])
+        .arg(Path::new("example_files").join("test_bibtex.tex"));
+
+    cmd.assert().code(2).stdout(
+        "@article{Higgs:2014aqa,\n    author = {Higgs, Peter W.},\n    year   = {2014}\n}\n",
+    );
+}
+
+#[test]
+fn tex2bib_stdin_stdout_offline_journal_names() {
+    let tmp_dir = cache_dir(&[(
+        "ads",
+        "1998MNRAS.301..759F",
+        "@ARTICLE{1998MNRAS.301..759F,\n  journal = {\\mnras},\n}\n",
+    )]);
+
+    offline("tex2bib", &tmp_dir)
+        .args(["--journal-names", "abbreviated"])
+        .with_stdin()
+        .buffer("\\cite{1998MNRAS.301..759F}\n")
+        .assert()
+        .success()
+        .stdout("@ARTICLE{1998MNRAS.301..759F,\n  journal = {MNRAS},\n}\n");
+}
+
+#[test]
+fn tex2bib_stdin_stdout_offline_doi() {
+    let tmp_dir = cache_dir(&[(
+        "doi",
+        "10.1103/PhysRevLett.116.061102",
+        "@article{10.1103/PhysRevLett.116.061102,\n}\n",
+    )]);
+    let report_file = tmp_dir.child("report.json");
+
+    offline("tex2bib", &tmp_dir)
+        .args(["--report", "json", "--report-file"])
+        .arg(report_file.path())
+        .with_stdin()
+        .buffer("\\cite{10.1103/PhysRevLett.116.061102}\n")
+        .assert()
+        .success()
+        .stdout("@article{10.1103/PhysRevLett.116.061102,\n}\n");
+
+    let report: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(report_file.path()).unwrap())
+            .expect("Valid JSON report");
+    assert_eq!(
+        report["resolved"]["DOI"],
+        json!(["10.1103/PhysRevLett.116.061102"])
+    );
+}
+
+#[test]
+fn tex2bib_stdin_stdout_offline_order() {
+    let tmp_dir = cache_dir(&[(
+        "inspire",
+        "1999PhRvL..83.3370R",
+        "@article{1999PhRvL..83.3370R,\n}\n",
+    )]);
+    let config = tmp_dir.child("config.toml");
+    config
+        .write_str("[order]\nads = [\"ads\", \"inspire\"]\n")
+        .unwrap();
+
+    // Only ADS is asked by default, and its entry is not cached
+    offline("tex2bib", &tmp_dir)
+        .env("XDG_CONFIG_HOME", tmp_dir.path())
+        .with_stdin()
+        .buffer("\\cite{1999PhRvL..83.3370R}\n")
+        .assert()
+        .code(2)
+        .stdout("");
+
+    offline("tex2bib", &tmp_dir)
+        .env("XDG_CONFIG_HOME", tmp_dir.path())
+        .args(["--order", "ads=ads,inspire"])
+        .with_stdin()
+        .buffer("\\cite{1999PhRvL..83.3370R}\n")
+        .assert()
+        .success()
+        .stdout("@article{1999PhRvL..83.3370R,\n}\n");
+
+    offline("tex2bib", &tmp_dir)
+        .env("XDG_CONFIG_HOME", tmp_dir.path())
+        .arg("--config")
+        .arg(config.path())
+        .with_stdin()
+        .buffer("\\cite{1999PhRvL..83.3370R}\n")
+        .assert()
+        .success()
+        .stdout("@article{1999PhRvL..83.3370R,\n}\n");
+
+    // With fallback, disabling ADS leaves INSPIRE to be asked
+    offline("tex2bib", &tmp_dir)
+        .env("XDG_CONFIG_HOME", tmp_dir.path())
+        .args(["--order", "ads=ads", "--disable", "ads", "--fallback"])
+        .with_stdin()
+        .buffer("\\cite{1999PhRvL..83.3370R}\n")
+        .assert()
+        .success()
+        .stdout("@article{1999PhRvL..83.3370R,\n}\n");
+
+    // A broken configuration is an error rather than silently ignored
+    config.write_str("fallbak = true\n").unwrap();
+    offline("tex2bib", &tmp_dir)
+        .arg("--config")
+        .arg(config.path())
+        .assert()
+        .code(1);
+
+    // So are misspelt sources
+    config.write_str("disabled = [\"arxive\"]\n").unwrap();
+    offline("tex2bib", &tmp_dir)
+        .arg("--config")
+        .arg(config.path())
+        .assert()
+        .code(1);
+    for args in &[["--disable", "arxive"], ["--order", "ads=ads,inspir"]] {
+        offline("tex2bib", &tmp_dir)
+            .env("XDG_CONFIG_HOME", tmp_dir.path())
+            .args(args)
+            .assert()
+            .code(1)
+            .stdout("");
+    }
+}
+
+#[test]
+fn tex2bib_file_stdout_offline_bib() {
+    let tmp_dir = cache_dir(&[("inspire", "Higgs:2014aqa", "@article{Higgs:2014aqa,\n}\n")]);
+    let master = tmp_dir.child("master.bib");
+    master
+        .write_str("@Article{Higgs:2015mei,\n  Title = {Curated by hand},\n}\n")
+        .unwrap();
+
+    let mut cmd = offline("tex2bib", &tmp_dir);
+    cmd.arg("--bib")
+        .arg(master.path())
+        .arg(Path::new("example_files").join("test_bibtex.tex"));
+
+    // Higgs:2015mei is not cached, but copied from the local database
+    cmd.assert().success().stdout(
+        "@article{Higgs:2014aqa,\n}\n@Article{Higgs:2015mei,\n  Title = {Curated by hand},\n}\n",
+    );
+}
+
+#[test]
+fn tex2bib_stdin_stdout_offline_bib_strings_crossref() {
+    let tmp_dir = assert_fs::TempDir::new().expect("can create tmp_dir");
+    let master = tmp_dir.child("master.bib");
+    let database = "@string{prl = \"Phys. Rev. Lett.\"}\n\
+                    @article{Higgs:2014aqa,\n  journal = prl,\n  crossref = {Proceedings}\n}\n\
+                    @proceedings{Proceedings,\n  booktitle = {Nobel Lectures},\n  year = 2014\n}\n";
+    master.write_str(database).unwrap();
+
+    // The entry is copied verbatim with what it depends on, and the parent only once
+    let assert = offline("tex2bib", &tmp_dir)
+        .arg("--no-cache")
+        .arg("--bib")
+        .arg(master.path())
+        .with_stdin()
+        .buffer("\\cite{Higgs:2014aqa,Proceedings}\n")
+        .assert()
+        .success()
+        .stdout(database);
+
+    // It can be used without the rest of the database
+    let bibtex = Bibtex::parse(std::str::from_utf8(&assert.get_output().stdout).unwrap())
+        .expect("Valid bibtex file content");
+    let bib = bibtex.bibliographies();
+    assert_eq!(bib.len(), 2);
+    assert_eq!(bib[0].citation_key(), "Higgs:2014aqa");
+    assert_eq!(
+        bib[0].tags(),
+        &vec![
+            ("journal".into(), "Phys. Rev. Lett.".into()),
+            ("crossref".into(), "Proceedings".into()),
+        ]
+    );
+    assert_eq!(bib[1].citation_key(), "Proceedings");
+
+    // Definitions already in the output file are not repeated
+    let tex = tmp_dir.child("paper.tex");
+    tex.write_str("\\cite{Higgs:2014aqa}\n").unwrap();
+    let output = tmp_dir.child("refs.bib");
+    output
+        .write_str("@string{PRL = {Phys. Rev. Lett.}}\n")
+        .unwrap();
+    offline("tex2bib", &tmp_dir)
+        .arg("--no-cache")
+        .arg("--bib")
+        .arg(master.path())
+        .arg(tex.path())
+        .arg(output.path())
+        .assert()
+        .success();
+    assert_eq!(
+        std::fs::read_to_string(output.path()).unwrap(),
+        format!(
+            "@string{{PRL = {{Phys. Rev. Lett.}}}}\n{}",
+            &database[database.find("@article").unwrap()..]
+        )
+    );
+}
+
+#[test]
+fn tex2bib_stdin_stdout_cache_ttl_too_long() {
+    let tmp_dir = cache_dir(&[]);
+
+    offline("tex2bib", &tmp_dir)
+        .args(["--cache-ttl", "999999999999999999"])
+        .assert()
+        .code(1)
+        .stdout("");
+}
+
+#[test]
+fn tex2bib_stdin_stdout_offline_bib_stale_cache() {
+    let tmp_dir = cache_dir(&[("inspire", "Higgs:2015mei", "@article{Higgs:2015mei,\n}\n")]);
+    let master = tmp_dir.child("master.bib");
+    master
+        .write_str("@Article{Higgs:2015mei,\n  Title = {Curated by hand},\n}\n")
+        .unwrap();
+
+    // The local database is asked before the cache
+    offline("tex2bib", &tmp_dir)
+        .arg("--bib")
+        .arg(master.path())
+        .with_stdin()
+        .buffer("\\cite{Higgs:2015mei}\n")
+        .assert()
+        .success()
+        .stdout("@Article{Higgs:2015mei,\n  Title = {Curated by hand},\n}\n");
+}
+
+#[test]
+fn tex2bib_file_stdout_offline_no_cache_bib() {
+    let tmp_dir = assert_fs::TempDir::new().expect("can create tmp_dir");
+    let master = tmp_dir.child("master.bib");
+    master
+        .write_str("@Article{Higgs:2015mei,\n  Title = {Curated by hand},\n}\n")
+        .unwrap();
+
+    let mut cmd = offline("tex2bib", &tmp_dir);
+    cmd.args(["--no-cache", "--bib"])
+        .arg(master.path())
+        .arg(Path::new("example_files").join("test_bibtex.tex"));
+
+    // Only the local database is used, so Higgs:2014aqa is left out
+    cmd.assert()
+        .code(2)
+        .stdout("@Article{Higgs:2015mei,\n  Title = {Curated by hand},\n}\n");
+    assert!(!tmp_dir.child("inspirer").path().exists());
+}
+
+#[test]
+fn tex2bib_file_stdout_offline_report_json() {
+    let tmp_dir = cache_dir(&[("inspire", "Higgs:2014aqa", "@article{Higgs:2014aqa,\n}\n")]);
+    let report_file = tmp_dir.child("report.json");
+
+    let mut cmd = offline("tex2bib", &tmp_dir);
+    cmd.args(["--report", "json", "--report-file"])
+        .arg(report_file.path())
+        .arg(Path::new("example_files").join("test_bibtex.tex"));
+
+    cmd.assert().code(2);
+
+    let report: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(report_file.path()).unwrap())
+            .expect("Valid JSON report");
+    assert_eq!(report["resolved"]["INSPIRE"], json!(["Higgs:2014aqa"]));
+    assert_eq!(report["failed"][0]["key"], "Higgs:2015mei");
+}
+
+#[test]
+fn tex2bib_file_file_offline_twice() {
+    let tmp_dir = cache_dir(&[("inspire", "Higgs:2014aqa", "@article{Higgs:2014aqa,\n}\n")]);
+    let output = tmp_dir.child("refs.bib");
+
+    // The second run must not append the entry again
+    for _ in 0..2 {
+        let mut cmd = offline("tex2bib", &tmp_dir);
+        cmd.arg(Path::new("example_files").join("test_bibtex.tex"))
+            .arg(output.path());
+        cmd.assert().code(2);
+    }
+    assert_eq!(
+        std::fs::read_to_string(output.path()).unwrap(),
+        "@article{Higgs:2014aqa,\n}\n"
+    );
+
+    Cache::new(tmp_dir.path().join("inspirer"))
+        .put(
+            "inspire",
+            "Higgs:2014aqa",
+            "@article{Higgs:2014aqa,\n  year = {2014}\n}\n",
+        )
+        .unwrap();
+    let mut cmd = offline("tex2bib", &tmp_dir);
+    cmd.args(["--replace", "--backup"])
+        .arg(Path::new("example_files").join("test_bibtex.tex"))
+        .arg(output.path());
+    cmd.assert().code(2);
+    assert_eq!(
+        std::fs::read_to_string(output.path()).unwrap(),
+        "@article{Higgs:2014aqa,\n  year = {2014}\n}\n"
+    );
+    assert_eq!(
+        std::fs::read_to_string(tmp_dir.child("refs.bib.bak").path()).unwrap(),
+        "@article{Higgs:2014aqa,\n}\n"
+    );
+}
+
+#[test]
+fn aux2bib_file_stdout_offline_format() {
+    let tmp_dir = cache_dir(&[(
+        "inspire",
+        "Higgs:2014aqa",
+        "@ARTICLE{Higgs:2014aqa,\n   YEAR = 2014,\n  author = \"Higgs, Peter W.\"\n}\n",
+    )]);
+
+    let mut cmd = offline("aux2bib", &tmp_dir);
+    cmd.args(["--format", "--indent", "4"])
+        .arg(Path::new("example_files").join("test_bibtex.aux"));
+
+    cmd.assert().code(2).stdout(
+        "@article{Higgs:2014aqa,\n    author = {Higgs, Peter W.},\n    year   = {2014}\n}\n",
+    );
+}
+
+#[test]
+fn blg2bib_stdin_stdout_offline_report_json() {
+    let tmp_dir = cache_dir(&[("inspire", "Higgs:2014aqa", "@article{Higgs:2014aqa,\n}\n")]);
+    let report_file = tmp_dir.child("report.json");
+
+    offline("blg2bib", &tmp_dir)
+        .args(["--report", "json", "--report-file"])
+        .arg(report_file.path())
+        .with_stdin()
+        .path(Path::new("example_files").join("test_bibtex.blg"))
+        .expect("example input file exists")
+        .assert()
+        .code(2)
+        .stdout("@article{Higgs:2014aqa,\n}\n");
+
+    let report: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(report_file.path()).unwrap())
+            .expect("Valid JSON report");
+    assert_eq!(report["resolved"]["INSPIRE"], json!(["Higgs:2014aqa"]));
+    assert_eq!(report["failed"][0]["key"], "Higgs:2015mei");
+}
+
+#[test]
+fn log2bib_file_file_offline_bib() {
+    let tmp_dir = cache_dir(&[("inspire", "Higgs:2014aqa", "@article{Higgs:2014aqa,\n}\n")]);
+    let master = tmp_dir.child("master.bib");
+    master
+        .write_str("@Article{Higgs:2015mei,\n  Title = {Curated by hand},\n}\n")
+        .unwrap();
+    let output = tmp_dir.child("refs.bib");
+
+    let mut cmd = offline("log2bib", &tmp_dir);
+    cmd.arg("--bib")
+        .arg(master.path())
+        .arg(Path::new("example_files").join("test_bibtex.log"))
+        .arg(output.path());
+
+    cmd.assert().success().stdout("");
+    assert_eq!(
+        std::fs::read_to_string(output.path()).unwrap(),
+        "@article{Higgs:2014aqa,\n}\n@Article{Higgs:2015mei,\n  Title = {Curated by hand},\n}\n"
+    );
+}
+
+#[test]
+fn aux2bib_file_stdout_missing_only() {
+    let tmp_dir = assert_fs::TempDir::new().expect("can create tmp_dir");
+    tmp_dir
+        .child("paper.aux")
+        .write_str("\\citation{Higgs:2014aqa}\n\\bibdata{refs}\n")
+        .unwrap();
+    tmp_dir
+        .child("refs.bib")
+        .write_str("@article{Higgs:2014aqa,\n}\n")
+        .unwrap();
+
+    // Nothing is missing, so nothing is fetched
+    let mut cmd = Command::cargo_bin("aux2bib").unwrap();
+    cmd.arg("--missing-only")
+        .arg(tmp_dir.child("paper.aux").path());
+    cmd.assert().success().stdout("");
+}
+
 #[test]
 fn aux2bib_stdin_stdout_bibtex() {
     let mut cmd = Command::cargo_bin("aux2bib").unwrap();